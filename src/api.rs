@@ -0,0 +1,221 @@
+// API (v1)
+// json in, json out, real status codes. the old /api is still in main.rs
+// for anyone who was using it
+
+use chrono::{Duration, Local, NaiveDate, TimeZone};
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::State;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+use crate::calendar::{CalendarStore, Registry, ISO_FMT};
+use crate::countdown::{self, Countdown};
+use crate::profile::{Profile, SavedProfile};
+use crate::schedule::{DayStatus, ScheduleDay};
+use crate::stat::{self, ClassStat, Stat, TermStat};
+use crate::terms::{Term, TermKind};
+use crate::whatif::{self, Edit, WhatIf};
+
+// don't let anyone ask for the whole century
+const MAX_RANGE_DAYS: i64 = 400;
+
+#[derive(Serialize, Debug)]
+pub struct ApiError {
+    pub error: &'static str,
+    pub message: String,
+}
+
+/// The stats section, plus every class, plus all of that per term.
+#[derive(Serialize, Debug)]
+pub struct Stats {
+    pub days: Stat,
+    pub classes: Vec<ClassStat>,
+    /// the ones `date` is in
+    pub term: Option<Term>,
+    pub semester: Option<Term>,
+    pub terms: Vec<TermStat>,
+}
+
+type ApiResult<T> = Result<Json<T>, status::Custom<Json<ApiError>>>;
+
+fn error(status: Status, error: &'static str, message: String) -> status::Custom<Json<ApiError>> {
+    status::Custom(status, Json(ApiError { error, message }))
+}
+
+/// A grade with no calendar is a 404, not the baked-in grade's schedule.
+fn check_grade(reg: &Registry, grade: Option<String>) -> Result<Option<String>, status::Custom<Json<ApiError>>> {
+    match grade {
+        Some(g) if !reg.knows(&g) => Err(error(Status::NotFound, "unknown_grade", format!("there's no grade {} calendar", g))),
+        g => Ok(g),
+    }
+}
+
+/// ISO dates (2022-03-21), or "today".
+fn parse_date(s: &str) -> Result<NaiveDate, status::Custom<Json<ApiError>>> {
+    if s == "today" {
+        return Ok(Local::now().naive_local().date());
+    }
+    NaiveDate::parse_from_str(s, ISO_FMT).map_err(|_| error(
+        Status::BadRequest,
+        "bad_date",
+        format!("'{}' isn't a date, use yyyy-mm-dd (or today)", s),
+    ))
+}
+
+#[get("/api/v1/day/<date>?<p>&<grade>")]
+pub fn day(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    date: String,
+    p: Option<String>,
+    grade: Option<String>,
+) -> ApiResult<ScheduleDay> {
+    let reg = store.get();
+    let date = parse_date(&date)?;
+    let cal = reg.select(check_grade(&reg, saved.grade(grade))?.as_deref(), date);
+    let profile = Profile::decode(cal, &saved.code(p));
+    if cal.day(&date).is_none() {
+        return Err(error(Status::NotFound, "not_in_calendar", format!("{} isn't in the calendar", date)));
+    }
+    Ok(Json(ScheduleDay::build(cal, date, Local::now().naive_local(), &profile)))
+}
+
+#[get("/api/v1/range?<from>&<to>&<p>&<grade>")]
+pub fn range(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    from: Option<String>,
+    to: Option<String>,
+    p: Option<String>,
+    grade: Option<String>,
+) -> ApiResult<Vec<ScheduleDay>> {
+    let reg = store.get();
+    let grade = check_grade(&reg, saved.grade(grade))?;
+    let code = saved.code(p);
+    let (from, to) = match (from, to) {
+        (Some(f), Some(t)) => (parse_date(&f)?, parse_date(&t)?),
+        _ => return Err(error(Status::BadRequest, "bad_range", "need both from and to".to_string())),
+    };
+    if to < from {
+        return Err(error(Status::BadRequest, "bad_range", "to is before from".to_string()));
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(error(Status::BadRequest, "bad_range", format!("that's more than {} days", MAX_RANGE_DAYS)));
+    }
+
+    // days outside the calendar still show up, just with no day type.
+    // a range can cross into next year's calendar
+    let now = Local::now().naive_local();
+    let days = (0..=(to - from).num_days())
+        .map(|i| {
+            let date = from + Duration::days(i);
+            let cal = reg.select(grade.as_deref(), date);
+            ScheduleDay::build(cal, date, now, &Profile::decode(cal, &code))
+        })
+        .collect();
+    Ok(Json(days))
+}
+
+/// The next day with classes that aren't cancelled, starting from `from` (today
+/// if there's no from). Today counts until school's out.
+#[get("/api/v1/next-school-day?<from>&<p>&<grade>")]
+pub fn next_school_day(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    from: Option<String>,
+    p: Option<String>,
+    grade: Option<String>,
+) -> ApiResult<ScheduleDay> {
+    let reg = store.get();
+    let code = saved.code(p);
+    let now = Local::now().naive_local();
+    let from = match from {
+        Some(f) => parse_date(&f)?,
+        None => now.date(),
+    };
+    let next = reg
+        .for_grade(check_grade(&reg, saved.grade(grade))?.as_deref())
+        .into_iter()
+        .flat_map(|cal| {
+            let profile = Profile::decode(cal, &code);
+            cal.days()
+                .filter(|(d, _)| **d >= from)
+                .map(move |(d, _)| ScheduleDay::build(cal, *d, now, &profile))
+        })
+        .find(|sd| sd.classes.is_some() && sd.cancelled.is_none() && sd.status != DayStatus::IsOver);
+    match next {
+        Some(sd) => Ok(Json(sd)),
+        None => Err(error(Status::NotFound, "no_school_days_left", "no more school days in the calendar".to_string())),
+    }
+}
+
+/// Stats for the school year `date` is in (today if there's no date), and for
+/// each of its terms, counted as of `date`.
+#[get("/api/v1/stats?<date>&<p>&<grade>")]
+pub fn stats(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    date: Option<String>,
+    p: Option<String>,
+    grade: Option<String>,
+) -> ApiResult<Stats> {
+    let reg = store.get();
+    let now = match date {
+        // start of the day, so that day's classes are still left
+        Some(d) => {
+            let date = parse_date(&d)?;
+            // midnight doesn't exist everywhere on the day the clocks change
+            Local.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap()).earliest().ok_or_else(|| error(
+                Status::BadRequest,
+                "bad_date",
+                format!("{} doesn't start at midnight here", date),
+            ))?
+        },
+        None => Local::now(),
+    };
+    let cal = reg.select(check_grade(&reg, saved.grade(grade))?.as_deref(), now.naive_local().date());
+    let profile = Profile::decode(cal, &saved.code(p));
+    let date = now.naive_local().date();
+    Ok(Json(Stats {
+        days: stat::generate_stat(cal, now, None),
+        classes: stat::class_stats(cal, now, &profile, None),
+        term: cal.term(&date, TermKind::Term).cloned(),
+        semester: cal.term(&date, TermKind::Semester).cloned(),
+        terms: stat::term_stats(cal, now, &profile),
+    }))
+}
+
+/// How long until the next holiday, ped day, exam day, special and the last day
+/// of classes. Small enough for a widget to poll.
+#[get("/api/v1/countdowns?<grade>")]
+pub fn countdowns(store: State<CalendarStore>, saved: SavedProfile, grade: Option<String>) -> ApiResult<Vec<Countdown>> {
+    let reg = store.get();
+    let now = Local::now().naive_local();
+    let cal = reg.select(check_grade(&reg, saved.grade(grade))?.as_deref(), now.date());
+    Ok(Json(countdown::countdowns(cal, now)))
+}
+
+/// What the schedule (and the stats) would look like after some edits, see
+/// whatif.rs. Nothing gets saved.
+#[get("/api/v1/whatif?<edits>&<weeks>&<p>&<grade>")]
+pub fn whatif(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    edits: Option<String>,
+    // a string so weeks=abc is a 400 and not the default
+    weeks: Option<String>,
+    p: Option<String>,
+    grade: Option<String>,
+) -> ApiResult<WhatIf> {
+    let bad_edit = |message| error(Status::BadRequest, "bad_edit", message);
+    let edits = Edit::parse_list(&edits.unwrap_or_default()).map_err(bad_edit)?;
+    let weeks = match weeks {
+        Some(w) => w.parse().map_err(|_| bad_edit(format!("'{}' isn't a number of weeks", w)))?,
+        None => whatif::DEFAULT_WEEKS,
+    };
+    // whichever calendar the first edit is in
+    let reg = store.get();
+    let first = edits.iter().flat_map(|e| e.dates()).min().unwrap_or_else(|| Local::now().naive_local().date());
+    let cal = reg.select(check_grade(&reg, saved.grade(grade))?.as_deref(), first);
+    whatif::simulate(cal, edits, Local::now(), &saved.code(p), weeks).map(Json).map_err(bad_edit)
+}
@@ -0,0 +1,203 @@
+// Bells
+// what time everything happens. used to be a list in the template and
+// some hours hard-coded in Block::generate, which of course disagreed
+
+use std::collections::BTreeMap;
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use crate::days::{Day, DayTypes};
+
+pub const BELLS_PATH:     &str = "./bells.csv";
+pub const BELL_DAYS_PATH: &str = "./bell_days.csv";
+
+const TIME_FMT: &str = "%H:%M";
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum SlotKind {
+    Class,
+    Lunch,
+    Break,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Slot {
+    pub kind: SlotKind,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    pub label: String,
+}
+
+impl Slot {
+    /// Class and lunch slots line up with the columns in the timetable,
+    /// breaks don't take up a column.
+    pub fn is_period(&self) -> bool {
+        self.kind != SlotKind::Break
+    }
+}
+
+/// "9:05-10:05", the way times go on the page.
+pub fn format_range(start: NaiveTime, end: NaiveTime) -> String {
+    format!("{}-{}", start.format("%-H:%M"), end.format("%-H:%M"))
+}
+
+/// What's going on at some point during a school day.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+pub struct Current {
+    /// the class (index into the day's classes) happening right now, if any
+    pub period: Option<usize>,
+    pub class: Option<String>,
+    /// the next class to start, if there's one left
+    pub next: Option<usize>,
+    pub next_class: Option<String>,
+    /// until whatever's happening now ends (or until the next thing starts, between classes)
+    pub minutes_left: i64,
+    pub in_break: bool,
+    pub in_lunch: bool,
+    pub label: String,
+}
+
+impl Current {
+    /// Fills in the class names. The bells don't know what the classes are.
+    pub fn with_classes(mut self, classes: &[String]) -> Self {
+        self.class = self.period.and_then(|p| classes.get(p).cloned());
+        self.next_class = self.next.and_then(|p| classes.get(p).cloned());
+        self
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BellSchedule {
+    pub name: String,
+    pub slots: Vec<Slot>,
+}
+
+impl BellSchedule {
+    /// Just the slots that have a class (or lunch) in them, in order.
+    pub fn periods(&self) -> Vec<&Slot> {
+        self.slots.iter().filter(|s| s.is_period()).collect()
+    }
+
+    pub fn start(&self) -> Option<NaiveTime> {
+        self.slots.first().map(|s| s.start)
+    }
+
+    pub fn end(&self) -> Option<NaiveTime> {
+        self.slots.last().map(|s| s.end)
+    }
+
+    /// Where we are in the day at time `t`. None before the first bell or after the last one.
+    pub fn current(&self, t: NaiveTime) -> Option<Current> {
+        if t < self.start()? || t >= self.end()? {
+            return None;
+        }
+        let count = self.periods().len();
+        let next_from = |i: usize| if i < count {Some(i)} else {None};
+        // round up, "0 min left" while it's still going is silly
+        let minutes_until = |end: NaiveTime| ((end - t).num_seconds() + 59) / 60;
+
+        let mut period = 0;
+        for slot in &self.slots {
+            if t < slot.start {
+                // between slots, nothing going on
+                return Some(Current {
+                    next: next_from(period),
+                    minutes_left: minutes_until(slot.start),
+                    in_break: true,
+                    ..Default::default()
+                });
+            }
+            if t < slot.end {
+                return Some(Current {
+                    period: if slot.is_period() {Some(period)} else {None},
+                    next: next_from(if slot.is_period() {period + 1} else {period}),
+                    minutes_left: minutes_until(slot.end),
+                    in_break: slot.kind == SlotKind::Break,
+                    in_lunch: slot.kind == SlotKind::Lunch,
+                    label: slot.label.clone(),
+                    ..Default::default()
+                });
+            }
+            if slot.is_period() {
+                period += 1;
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Bells {
+    schedules: BTreeMap<String, BellSchedule>,
+    by_day: Vec<(Day, String)>,
+}
+
+impl Bells {
+    /// Adds one row of bells.csv. Rows for the same schedule have to be in order.
+    pub fn add_slot(&mut self, record: &csv::StringRecord) -> Result<(), (usize, String)> {
+        let name = record.get(0).unwrap_or_default();
+        if name.is_empty() {
+            return Err((1, "no schedule name".to_string()));
+        }
+        let kind = match record.get(1).unwrap_or_default() {
+            "class" => SlotKind::Class,
+            "lunch" => SlotKind::Lunch,
+            "break" => SlotKind::Break,
+            k => return Err((2, format!("'{}' isn't class, lunch or break", k))),
+        };
+        let start = Self::parse_time(record.get(2), 3)?;
+        let end = Self::parse_time(record.get(3), 4)?;
+        if end <= start {
+            return Err((4, "ends before it starts".to_string()));
+        }
+
+        let schedule = self.schedules.entry(name.to_string()).or_insert_with(|| BellSchedule {
+            name: name.to_string(),
+            slots: vec![],
+        });
+        if let Some(prev) = schedule.slots.last() {
+            if start < prev.end {
+                return Err((3, format!("starts before the previous slot in '{}' ends", name)));
+            }
+        }
+        schedule.slots.push(Slot {
+            kind,
+            start,
+            end,
+            label: record.get(4).unwrap_or_default().to_string(),
+        });
+        Ok(())
+    }
+
+    /// Adds one row of bell_days.csv (`day code,schedule`). Do this after all the slots.
+    pub fn add_day(&mut self, record: &csv::StringRecord, day_types: &DayTypes) -> Result<(), (usize, String)> {
+        let code = record.get(0).unwrap_or_default();
+        let day = day_types.parse(code);
+        if let Day::Unknown = day {
+            return Err((1, format!("unknown day code '{}'", code)));
+        }
+        let name = record.get(1).unwrap_or_default();
+        if !self.schedules.contains_key(name) {
+            return Err((2, format!("no bell schedule called '{}'", name)));
+        }
+        self.by_day.push((day, name.to_string()));
+        Ok(())
+    }
+
+    fn parse_time(s: Option<&str>, column: usize) -> Result<NaiveTime, (usize, String)> {
+        let s = s.unwrap_or_default();
+        NaiveTime::parse_from_str(s.trim(), TIME_FMT)
+            .map_err(|_| (column, format!("bad time '{}' (should be hh:mm)", s)))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&BellSchedule> {
+        self.schedules.get(name)
+    }
+
+    pub fn for_day(&self, day: &Day) -> Option<&BellSchedule> {
+        self.by_day
+            .iter()
+            .find(|(d, _)| d == day)
+            .and_then(|(_, name)| self.get(name))
+    }
+}
@@ -1,21 +1,22 @@
-
 // Blocks
+// the page's version of a ScheduleDay: colors, greetings, times as text. none
+// of that belongs in the actual schedule. the only html in here is specials,
+// and that goes through markup.rs
 
-use chrono::{DateTime, Local, NaiveDate, Timelike};
 use rand::prelude::SliceRandom;
 use serde::{Deserialize, Serialize};
+use crate::bells::{self, Current};
+use crate::days::{Day, DayTypes};
+use crate::markup;
+use crate::schedule::{Cancellation, ScheduleDay};
+use crate::specials::Special;
 
-const SPECIALS_PATH: &str = "./special.csv";
-const ONLINE_PATH:   &str = "./online.csv";
-const LOOKUP_PATH:   &str = "./lookup.csv";
-const SCHED_CLASSES: &str = include_str!("sched_classes.csv");
-const SCHED_DATA:    &str = include_str!("sched_data_11.csv");
-
+/// One class on the page. The template does the bold and italics.
 #[derive(Serialize, Deserialize, Debug)]
-enum BlockStatus {
-    NotStarted = 0,
-    Normal = 1,
-    IsOver = 2,
+pub struct BlockClass {
+    name: String,
+    substituted: bool,
+    note: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -26,8 +27,13 @@ pub struct Block {
     greeting: String,
     day: Option<Day>,
     day_str: String,
-    classes: Vec<String>,
+    classes: Vec<BlockClass>,
     classes_is_some: bool,
+    times: Vec<String>,
+    current_is_some: bool,
+    current_period: i32,
+    current_str: String,
+    /// already html (see markup.rs)
     special: Vec<String>,
     special_is_some: bool,
     status: i32,
@@ -35,350 +41,123 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn generate(dt: DateTime<Local>, now: DateTime<Local>, title: &str) -> Self {
-        // what day is it? etc
-        let date = dt.naive_local().date();
-
-        let day = Self::day_from_date(&date);
-        let mut day_str = Self::format_day(&day);
-
-
-        let mut classes = match &day {
-            Some(d) => Self::classes_from_day(d),
-            None => None,
+    pub fn from_day(sd: &ScheduleDay, day_types: &DayTypes, title: &str) -> Self {
+        let greeting = if title == "Today" {
+            "I hope you have a nice day.".to_string()
+        } else {
+            Self::rand_greeting()
         };
-        // this needs its own variable so we can operate on it
-        // before it gets stolen by the struct to find out if
-        // it is some (since i don't think the template is smart
-        // enough for this)
-        let classes_is_some = classes.is_some();
-
-        let special = Self::get_special(&date);
-        let special_is_some = special.is_some();
 
-        //let is_online = Self::check_online(&date);
-
-
-        // stupid hack (?) to make a very clear way to cancel a day
-        // or other VERY SPECIAL EVENTS that require a whole block change
-        if special_is_some {
-            let s = special.clone().unwrap();
-            let first_spec = s.first();
-
-            match first_spec {
-                Some(c) => match c.as_str() {
-                    "*CANC" => {
-                        // day cancelled
-                        return Block {
-                            date: date.format("%A, %d-%b-%Y").to_string(),
-                            title: title.to_string(),
-                            bgcolorcode: "#aaaaaa".to_string(),
-                            greeting:   if title == "Today" {
-                                "I hope you have a nice day.".to_string()
-                            } else {
-                                Self::rand_greeting()
-                            },
-                            day,
-                            day_str: "CANCELLED".to_string(),
-                            classes: vec![],
-                            classes_is_some: false,
-                            special: special.unwrap_or_default(),
-                            special_is_some: false,
-                            status: BlockStatus::Normal as i32,
-                            //is_online
-                        }
-                    },
-                    "*CANCSNOW" => {
-                        // day cancelled (snow day)
-                        // ... just in case.
-                        return Block {
-                            date: date.format("%A, %d-%b-%Y").to_string(),
-                            title: title.to_string(),
-                            bgcolorcode: "#bf6565".to_string(),
-                            greeting: "I hope you have a nice day.".to_string(),
-                            day,
-                            day_str: "Snow day!".to_string(),
-                            classes: vec![],
-                            classes_is_some: false,
-                            special: special.unwrap_or_default(),
-                            special_is_some: false,
-                            status: BlockStatus::Normal as i32,
-                            //is_online
-                        }
-                    },
-                    _ => {}
+        // cancelled days get a whole different block
+        if let Some(c) = &sd.cancelled {
+            let (day_str, greeting) = match c.kind {
+                Cancellation::Cancelled => ("CANCELLED", greeting),
+                // ... just in case.
+                Cancellation::SnowDay => ("Snow day!", "I hope you have a nice day.".to_string()),
+            };
+            return Block {
+                date: sd.date.format("%A, %d-%b-%Y").to_string(),
+                title: title.to_string(),
+                bgcolorcode: Self::bgcolor(sd, day_types),
+                greeting,
+                day: sd.day.clone(),
+                day_str: match &c.reason {
+                    Some(r) => format!("{} ({})", day_str, r),
+                    None => day_str.to_string(),
                 },
-                None => {}
+                classes: vec![],
+                classes_is_some: false,
+                times: vec![],
+                current_is_some: false,
+                current_period: -1,
+                current_str: String::new(),
+                special: sd.specials.iter().map(Self::format_special).collect(),
+                special_is_some: false,
+                status: sd.status as i32,
+                //is_online
             };
         }
 
+        let classes = sd.classes.clone().unwrap_or_default();
+        let bgcolorcode = Self::bgcolor(sd, day_types);
 
-        let status = match &day {
-            Some(d) => {
-                match d {
-                    Day::Day1 |
-                    Day::Day2 |
-                    Day::Day3 |
-                    Day::Day4 |
-                    Day::Day5 |
-                    Day::Day6 |
-                    Day::Day7 |
-                    Day::Day8 => {
-                        if dt.hour() < 8
-                            && dt.naive_local().date() == now.naive_local().date() {
-                            BlockStatus::NotStarted
-                        } else if dt.hour() >= 16
-                            && dt.naive_local().date() == now.naive_local().date() {
-                            BlockStatus::IsOver
-                        } else {
-                            BlockStatus::Normal
-                        }
-                    }
-                    Day::Day9 => {
-                        if dt.hour() < 8
-                            && dt.naive_local().date() == now.naive_local().date() {
-                            BlockStatus::NotStarted
-                        } else if dt.hour() >= 13 // safe estimates
-                            && dt.naive_local().date() == now.naive_local().date() {
-                            BlockStatus::IsOver
-                        } else {
-                            BlockStatus::Normal
-                        }
-                    }
-                    Day::Ped |
-                    Day::Holiday |
-                    Day::HolidayDontCount |
-                    Day::Weekend |
-                    Day::Exam | Day::NoExam | 
-                    Day::Unknown => BlockStatus::Normal // not over, it never started
-                }
-            },
-            None => BlockStatus::Normal
-        };
-
-
-        // alter
-        let alter = LookupAlter::alter(&date, classes).unwrap();
-        classes = alter.html(); // やべ x2
-        if let Some(c) = alter.ctd {
-            day_str = c;
-        }
-        let bgcolorcode = if let Some(ctdc) = alter.ctdcolor {
-            ctdc
-        } else {
-            match &day {
-                Some(d) => Self::bgcolorcode(d),
-                None => "#2b3032".to_string() // default
-            }
-        };
-
-        // generate struct
         Block {
-            date: date.format("%A, %d-%b-%Y").to_string(),
+            date: sd.date.format("%A, %d-%b-%Y").to_string(),
             title: title.to_string(),
             bgcolorcode,
-            greeting:   if title == "Today" {
-                            "I hope you have a nice day.".to_string()
-                        } else {
-                            Self::rand_greeting()
-                        },
-            day,
-            day_str,
-            classes: classes.unwrap_or_default(),
-            classes_is_some,
-            special: special.unwrap_or_default(),
-            special_is_some,
-            status: status as i32,
+            greeting,
+            day: sd.day.clone(),
+            day_str: sd.label.clone(),
+            classes: classes
+                .iter()
+                .map(|c| BlockClass {
+                    name: c.name.clone(),
+                    substituted: c.substituted,
+                    note: c.note.clone(),
+                })
+                .collect(),
+            // the template isn't smart enough to check for none itself
+            classes_is_some: sd.classes.is_some(),
+            times: classes
+                .iter()
+                .map(|c| match (c.start, c.end) {
+                    (Some(s), Some(e)) => bells::format_range(s, e),
+                    _ => String::new(),
+                })
+                .collect(),
+            current_is_some: sd.current.is_some(),
+            current_period: sd.current.as_ref().and_then(|c| c.period).map(|p| p as i32).unwrap_or(-1),
+            current_str: sd.current.as_ref().map(Self::format_current).unwrap_or_default(),
+            special: sd.specials.iter().map(Self::format_special).collect(),
+            special_is_some: !sd.specials.is_empty(),
+            status: sd.status as i32,
             //is_online
         }
     }
-    pub fn classes_from_day(day: &Day) -> Option<Vec<String>> {
-        match day {
-            Day::Ped |
-            Day::Holiday |
-            Day::HolidayDontCount |
-            Day::Weekend |
-            Day::NoExam |
-            Day::Unknown => {
-                return None;
-            },
-            Day::Exam => {return Some(vec![" ".to_string()])},
-            _ => {}
-        }
-
-        let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(SCHED_CLASSES.as_bytes());
 
-        let r = reader.records().nth(match day {
-            Day::Day1 => 0,
-            Day::Day2 => 1,
-            Day::Day3 => 2,
-            Day::Day4 => 3,
-            Day::Day5 => 4,
-            Day::Day6 => 5,
-            Day::Day7 => 6,
-            Day::Day8 => 7,
-            Day::Day9 => 8,
-            _ => unreachable!() // i hope
-        }).unwrap().unwrap();
-        // we have the line
-        // turn it into Vec of string
-        let mut t: Vec<String> = r.iter().map(|x| x.to_string()).collect();
-        for item in t.clone().iter().rev() { // remove empties
-            // right it's a csv thing they all have to have the same amount of rows
-            // but we don't want blank items
-            if item == &"".to_string() {
-                t.pop(); // this sounds good but COULD FAIL BE CAREFUL I'M NOT 100% SURE ON THIS ONE
-                // basically I think it just needs to be empty only at the end
-                // like we can't have empty ones in the middle or this will mess up badly
-            }
-        }
-        Some(t)
-    }
-    pub fn day_from_date(now: &NaiveDate) -> Option<Day> {
-        let now_str = now.format("%d-%m-%Y").to_string();
-        let mut reader = csv::Reader::from_reader(SCHED_DATA.as_bytes());
-        for r in reader.records() {
-            let record = r.unwrap();
-            if record.get(0).is_some() {
-                let x =  record.get(0).unwrap();
-                if x == now_str {
-                    return Some(
-                        // we are assuming that our data is all good. since, you know,
-                        // it's included in the binary and i'm not about to change it for now
-                        // so we can assume stuff like "if there's a value at 0, there will FOR
-                        // SURE be one at index 1" stuff like that
-                        match record.get(1).expect("corrupt sched csv or sum idk").trim() {
-                            "1" => Day::Day1,
-                            "2" => Day::Day2,
-                            "3" => Day::Day3,
-                            "4" => Day::Day4,
-                            "5" => Day::Day5,
-                            "6" => Day::Day6,
-                            "7" => Day::Day7,
-                            "8" => Day::Day8,
-                            "9" => Day::Day9,
-                            "P" => Day::Ped,
-                            "C" => Day::Holiday,
-                            "D" => Day::HolidayDontCount,
-                            "W" => Day::Weekend,
-                            "E" => Day::Exam,
-"N" => Day::NoExam,
-                            _ => Day::Unknown,
-                        }
-                    );
-                }
-            }
+    /// What color a day is, here and on the month/year pages.
+    pub fn bgcolor(sd: &ScheduleDay, day_types: &DayTypes) -> String {
+        match (&sd.cancelled, &sd.ctd, &sd.day) {
+            (Some(c), _, _) => match c.kind {
+                Cancellation::Cancelled => "#aaaaaa",
+                Cancellation::SnowDay => "#bf6565",
+            }.to_string(),
+            (None, Some(ctd), _) => ctd.color.clone(),
+            (None, None, Some(d)) => day_types.color(d),
+            (None, None, None) => "#2b3032".to_string() // default
         }
-        None
     }
 
-    pub fn get_special(date: &NaiveDate) -> Option<Vec<String>> {
-        let mut reader = match csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_path(SPECIALS_PATH)
-        {
-            Ok(r) => r,
-            Err(_) => return None, // right, basically this will get updated on runtime so we don't want
-            // it to die on us. so just be careful around here, use default, etc
-        };
-
-        let date_str = date.format("%d-%m-%Y").to_string();
-        let mut specials: Vec<String> = vec![];
-        for r in reader.records() {
-            let record = r.unwrap_or_default();
-            match record.get(0) {
-                Some(r) => {
-                    if r == date_str {
-                        // we good here
-                        specials
-                            .push(record.get(1)
-                                .unwrap_or("oh no! error code 1 or something idk basically I made a mistake")
-                                .to_string()
-                            )
-                    }
-                },
-                None => {}
-            }
+    // "Assembly 9:00-10:00 @ gym (grade 11)", as html
+    fn format_special(s: &Special) -> String {
+        let mut out = markup::to_html(&s.text);
+        match (s.start, s.end) {
+            (Some(st), Some(e)) => out += &format!(" {}", bells::format_range(st, e)),
+            (Some(st), None) => out += &format!(" at {}", st.format("%-H:%M")),
+            (None, Some(e)) => out += &format!(" until {}", e.format("%-H:%M")),
+            (None, None) => {},
         }
-        if specials.len() > 0 {
-            Some(specials)
-        } else {
-            None
+        if let Some(l) = &s.location {
+            out += &format!(" @ {}", markup::escape(l));
         }
-    }
-
-    // i want to remove this so bad. i commented all usages out
-    // (ao Fri 27-Aug-2021 09:32 PM) but i'll leave the function etc here.
-    // just in case.
-    pub fn check_online(date: &NaiveDate) -> bool {
-        let mut reader = match csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_path(ONLINE_PATH)
-        {
-            Ok(r) => r,
-            Err(_) => return false, // like specials, this will get updated. assume false as much as possible
-        };
-
-        let date_str = date.format("%d-%m-%Y").to_string();
-        for r in reader.records() {
-            let record = r.unwrap_or_default();
-            match record.get(0) {
-                Some(r) => {
-                    if r == date_str {
-                        return true
-                    }
-                }
-                None => {}
-            }
+        if let Some(a) = &s.audience {
+            out += &format!(" ({})", markup::escape(a));
         }
-        false
+        out
     }
 
-    pub fn format_day(day: &Option<Day>) -> String {
-        match day {
-            Some(d) => match d {
-                Day::Day1 => "Day 1",
-                Day::Day2 => "Day 2",
-                Day::Day3 => "Day 3",
-                Day::Day4 => "Day 4",
-                Day::Day5 => "Day 5",
-                Day::Day6 => "Day 6",
-                Day::Day7 => "Day 7",
-                Day::Day8 => "Day 8",
-                Day::Day9 => "Day 9 (half day!)",
-                Day::Ped => "a Ped Day",
-                Day::Holiday | Day::HolidayDontCount
-                    => "a Holiday of Some Sort, idk look it up in the calendar",
-                Day::Weekend => "the weekend",
-Day::Exam => "an Exam Day",
-Day::NoExam => "an Exam-free Day",
-                Day::Unknown => "unknown ???",
-            }.to_string(),
-            None => "no day".to_string(),
+    fn format_current(c: &Current) -> String {
+        let next = match &c.next_class {
+            Some(n) => format!(" Next up: {}.", n),
+            None => " That's the last one!".to_string(),
+        };
+        match (&c.class, c.in_break) {
+            (Some(class), _) => format!("{} right now, {} min left.{}", class, c.minutes_left, next),
+            (None, true) if !c.label.is_empty() => format!("{} right now, {} min left.{}", c.label, c.minutes_left, next),
+            _ => format!("Between classes, {} min until the next one.{}", c.minutes_left, next),
         }
     }
 
-    fn bgcolorcode(day: &Day) -> String {
-        match day {
-            Day::Day1 => "#ad253e", //"#5b140b",
-            Day::Day2 => "#6a4823",
-            Day::Day3 => "#296a33",
-            Day::Day4 => "#2f6a5f",
-            Day::Day5 => "#29556a",
-            Day::Day6 => "#3d386a",
-            Day::Day7 => "#6a3a62",
-            Day::Day8 => "#79141e",
-            Day::Day9 => "#56617a",
-
-            Day::Ped => "#549ac6",
-            Day::Holiday | Day::HolidayDontCount => "#c68252",
-            Day::Weekend => "#2b3032",
-            Day::Exam => "#39b938",
-            Day::NoExam => "#4e88a3",
-            Day::Unknown => "#FF0000", // should never see this
-        }.to_string()
-    }
-
     fn rand_greeting() -> String {
         let greetings = vec![
             "I hope you have a great day.",
@@ -411,109 +190,3 @@ Day::NoExam => "an Exam-free Day",
         greetings.choose(&mut rand::thread_rng()).unwrap().to_string()
     }
 }
-
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub enum Day {
-    Day1 = 0,
-    Day2 = 1,
-    Day3 = 2,
-    Day4 = 3,
-    Day5 = 4,
-    Day6 = 5,
-    Day7 = 6,
-    Day8 = 7,
-    Day9 = 8,
-    Ped,
-    Holiday,
-    HolidayDontCount,
-    Weekend,
-    Exam,
-    NoExam,
-    Unknown
-}
-
-struct LookupAlter {
-    pub classes: Option<Vec<String>>,
-    pub ctd: Option<String>,
-    pub ctdcolor: Option<String>,
-}
-
-impl LookupAlter {
-    pub fn alter(date: &NaiveDate, classes: Option<Vec<String>>) -> Result<Self, ()> {
-        let mut reader = match csv::ReaderBuilder::new()
-            .has_headers(false)
-            .from_path(LOOKUP_PATH)
-        {
-            Ok(r) => r,
-            Err(_) => return Err(()),
-        };
-        let date_str = date.format("%d-%m-%Y").to_string();
-
-        let mut newclasses = classes.clone().unwrap_or_default();
-        let mut ctd = ""; // "change the day"
-        let mut ctdcolor = "";
-
-        for r in reader.records() {
-            let record = r.unwrap_or_default();
-            if record.get(0).unwrap_or_default() == date_str {
-                let r1 = record.get(1).unwrap_or_default();
-                let p = r1.parse::<usize>();
-                if p.is_ok() && classes.is_some() {
-                    newclasses[p.unwrap() - 1] = "$".to_owned() + record.get(2).unwrap_or_default();
-                } else if r1 == "CTD" {
-                    match record.get(2) {
-                        Some(x) => {
-                            match x {
-                                "ProductionWeek" => {
-                                    for (i, class) in classes.clone().unwrap().iter().enumerate() {
-                                        if !(class == "Chant" || class == "Instro" || class == "Lunch") {
-                                            newclasses[i] = " ".to_string();
-                                        }
-                                    }
-                                    ctd = "a Production Day";
-                                    ctdcolor = "#4e94af";
-                                },
-                                "ProductionWeekShow" => {
-                                    for (i, class) in classes.clone().unwrap().iter().enumerate() {
-					if !(class == "Lunch" || (class == "Instro" && i<4)) {
-						newclasses[i] = " ".to_string();
-}
-}
-				    ctd = "a Show!";
-                                    ctdcolor = "#cb762d";
-                                },
-                                "Fasho" => {
-                                    ctd = "the Fashion Show!";
-                                    ctdcolor = "#c65454";
-                                },
-                                _ => {}
-                            }
-                        }
-                        None => {}
-                    }
-                }
-            }
-        }
-
-        Ok(Self {
-            classes: if classes.is_some() {Some(newclasses)} else {None},
-            ctd: if ctd == "" {None} else {Some(ctd.to_string())},
-            ctdcolor: if ctdcolor == "" {None} else {Some(ctdcolor.to_string())} //4e94af
-        })
-    }
-
-    pub fn html(&self) -> Option<Vec<String>> {
-        if self.classes.is_none() {
-            return None
-        }
-        let mut htmlclasses: Vec<String> = vec![];
-        for class in self.classes.as_ref().unwrap() {
-            if class.starts_with("$") { // ya ok this kinda sucks
-                htmlclasses.push(format!("<b><i>{}</i></b>", class.trim_matches('$')));
-            } else {
-                htmlclasses.push(class.to_string());
-            }
-        }
-        Some(htmlclasses)
-    }
-}
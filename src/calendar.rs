@@ -0,0 +1,632 @@
+// Calendar
+// everything gets loaded into here ONCE instead of every block re-reading
+// the same csv files over and over (160 blocks * 5 files... yeah)
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::io::ErrorKind;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Datelike, NaiveDate};
+use crate::bells::{BellSchedule, Bells, BELLS_PATH, BELL_DAYS_PATH};
+use crate::ctd::{CtdModes, CTD_MODES_PATH};
+use crate::days::{Day, DayTypes, DAY_TYPES_PATH};
+use crate::lookup::{Action, LookupRow};
+use crate::recur::Recurrence;
+use crate::schedule::{CancelMode, Cancellation};
+use crate::specials::{Cancelled, Special, Specials};
+use crate::terms::{Term, TermKind, TERMS_PATH};
+
+pub const SPECIALS_PATH: &str = "./special.csv";
+pub const ONLINE_PATH:   &str = "./online.csv";
+pub const LOOKUP_PATH:   &str = "./lookup.csv";
+pub const CANCEL_MODES_PATH: &str = "./cancel_modes.csv";
+pub const CALENDARS_PATH: &str = "./calendars.csv";
+pub const RECURRING_PATH: &str = "./recurring.csv";
+const SCHED_CLASSES: &str = include_str!("sched_classes.csv");
+const SCHED_DATA:    &str = include_str!("sched_data_11.csv");
+
+// the baked-in calendar, for anyone who doesn't say which grade they're in
+pub const BUILTIN_GRADE: &str = "11";
+
+pub const DATE_FMT: &str = "%d-%m-%Y";
+// everywhere that isn't a csv file: the api, urls
+pub const ISO_FMT:  &str = "%Y-%m-%d";
+
+// how often the watcher looks at the runtime files
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Something wrong with one of the runtime files.
+#[derive(Debug)]
+pub struct LoadError {
+    pub file: String,
+    pub line: u64,
+    pub msg: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.msg)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Calendar {
+    grade: String,
+    year: String,
+    days: BTreeMap<NaiveDate, Day>,
+    timetable: Vec<Vec<String>>,
+    specials: Specials,
+    cancel_modes: Vec<(Cancellation, CancelMode)>,
+    online: BTreeSet<NaiveDate>,
+    lookup: BTreeMap<NaiveDate, Vec<LookupRow>>,
+    // recurring.csv, worked out day by day so they follow the rotation
+    recurring_specials: Vec<(Recurrence, Special)>,
+    recurring_lookup: Vec<(Recurrence, LookupRow)>,
+    bells: Bells,
+    ctd_modes: CtdModes,
+    day_types: DayTypes,
+    // every row of terms.csv until with_source keeps just this calendar's
+    terms: Vec<Term>,
+}
+
+impl Calendar {
+    /// Loads the runtime files everyone shares (no days or classes yet), or
+    /// fails if any of them are broken.
+    fn load_shared() -> Result<Self, LoadError> {
+        let mut cal = Calendar::default();
+
+        // first, because nothing else means anything without it
+        for (line, record) in Self::read_runtime(DAY_TYPES_PATH)? {
+            cal.day_types.add(&record).map_err(|(_, msg)| Self::err(DAY_TYPES_PATH, line, &msg))?;
+        }
+        if cal.day_types.is_empty() {
+            return Err(Self::err(DAY_TYPES_PATH, 0, "no day types, is the file missing?"));
+        }
+
+        // the runtime stuff. these get edited while the server is up, so
+        // a missing file is just empty but a broken one is an error
+        for (line, record) in Self::read_runtime(SPECIALS_PATH)? {
+            cal.specials.add(&record).map_err(|(_, msg)| Self::err(SPECIALS_PATH, line, &msg))?;
+        }
+
+        for (line, record) in Self::read_runtime(CANCEL_MODES_PATH)? {
+            let c = Cancellation::from_sentinel(record.get(0).unwrap_or_default())
+                .ok_or_else(|| Self::err(CANCEL_MODES_PATH, line, "not *CANC or *CANCSNOW"))?;
+            let mode = match record.get(1).unwrap_or_default().trim() {
+                "skip" => CancelMode::Skip,
+                "shift" => CancelMode::Shift,
+                m => return Err(Self::err(CANCEL_MODES_PATH, line, &format!("'{}' isn't skip or shift", m))),
+            };
+            cal.cancel_modes.push((c, mode));
+        }
+
+        for (line, record) in Self::read_runtime(ONLINE_PATH)? {
+            cal.online.insert(Self::parse_date(ONLINE_PATH, line, record.get(0))?);
+        }
+
+        for (line, record) in Self::read_runtime(BELLS_PATH)? {
+            cal.bells.add_slot(&record).map_err(|(_, msg)| Self::err(BELLS_PATH, line, &msg))?;
+        }
+        for (line, record) in Self::read_runtime(BELL_DAYS_PATH)? {
+            cal.bells.add_day(&record, &cal.day_types).map_err(|(_, msg)| Self::err(BELL_DAYS_PATH, line, &msg))?;
+        }
+        for (line, record) in Self::read_runtime(CTD_MODES_PATH)? {
+            cal.ctd_modes.add(&record, &cal.bells).map_err(|(_, msg)| Self::err(CTD_MODES_PATH, line, &msg))?;
+        }
+
+        for (line, record) in Self::read_runtime(LOOKUP_PATH)? {
+            let date = Self::parse_date(LOOKUP_PATH, line, record.get(0))?;
+            let row = LookupRow::parse(&record, 1, &cal.bells, &cal.ctd_modes, LOOKUP_PATH, line).map_err(|(_, msg)| Self::err(LOOKUP_PATH, line, &msg))?;
+            cal.lookup.entry(date).or_insert_with(Vec::new).push(row);
+        }
+
+        for (line, record) in Self::read_runtime(RECURRING_PATH)? {
+            let err = |(_, msg): (usize, String)| Self::err(RECURRING_PATH, line, &msg);
+            let recurrence = Recurrence::parse(&record, &cal.day_types).map_err(err)?;
+            match record.get(0).unwrap_or_default() {
+                "SPECIAL" => {
+                    let special = Specials::parse_recurring(&record).map_err(err)?;
+                    cal.recurring_specials.push((recurrence, special));
+                },
+                "LOOKUP" => {
+                    let row = LookupRow::parse(&record, 5, &cal.bells, &cal.ctd_modes, RECURRING_PATH, line).map_err(err)?;
+                    cal.recurring_lookup.push((recurrence, row));
+                },
+                what => return Err(err((1, format!("'{}' isn't SPECIAL or LOOKUP", what)))),
+            }
+        }
+
+        for (line, record) in Self::read_runtime(TERMS_PATH)? {
+            let mut term = Term::parse(&record).map_err(|(_, msg)| Self::err(TERMS_PATH, line, &msg))?;
+            if let Some(path) = &term.classes_name {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| Self::err(TERMS_PATH, line, &format!("can't read '{}' ({})", path, e)))?;
+                term.timetable = Some(Self::read_timetable(path, &text)?);
+            }
+            cal.terms.push(term);
+        }
+
+        Ok(cal)
+    }
+
+    /// This calendar, plus one source's days and classes.
+    fn with_source(&self, source: &Source) -> Result<Self, LoadError> {
+        let mut cal = self.clone();
+        cal.grade = source.grade.clone();
+
+        let reader = csv::ReaderBuilder::new().has_headers(false).from_reader(source.data.as_bytes());
+        for (line, record) in Self::records(&source.data_name, reader)? {
+            let date = Self::parse_date(&source.data_name, line, record.get(0))?;
+            cal.days.insert(date, cal.day_types.parse(record.get(1).unwrap_or_default()));
+        }
+        cal.year = match cal.days.keys().next() {
+            Some(first) => school_year(first),
+            None => return Err(Self::err(&source.data_name, 0, "no days in this calendar")),
+        };
+        cal.reflow();
+        cal.timetable = Self::read_timetable(&source.classes_name, &source.classes)?;
+
+        // just the terms for this grade and this year
+        let (first, last) = (cal.first(), cal.last());
+        cal.terms.retain(|t| t.is_for(&source.grade) && Some(t.from) <= last && Some(t.to) >= first);
+
+        Ok(cal)
+    }
+
+    /// Just a source with the day types and bells it's given, none of the
+    /// runtime files. For tests that shouldn't change when special.csv does.
+    #[cfg(test)]
+    pub fn from_parts(source: &Source, day_types: DayTypes, bells: Bells) -> Result<Self, LoadError> {
+        Calendar {
+            day_types,
+            bells,
+            ..Default::default()
+        }.with_source(source)
+    }
+
+    /// A timetable csv (sched_classes.csv, or a term's), one row per cycle day.
+    fn read_timetable(name: &str, text: &str) -> Result<Vec<Vec<String>>, LoadError> {
+        let reader = csv::ReaderBuilder::new().has_headers(false).from_reader(text.as_bytes());
+        let mut timetable = vec![];
+        for (_, record) in Self::records(name, reader)? {
+            let mut row: Vec<String> = record.iter().map(|x| x.to_string()).collect();
+            // it's a csv thing, every row has the same amount of columns,
+            // but we don't want the blank ones off the end
+            while row.last().map(|x| x.is_empty()).unwrap_or(false) {
+                row.pop();
+            }
+            timetable.push(row);
+        }
+        Ok(timetable)
+    }
+
+    /// Moves the rotation along for every cancelled cycle day that shifts:
+    /// the next school day takes its number, and so on to the end.
+    fn reflow(&mut self) {
+        let len = self.day_types.cycle_length();
+        let modes = &self.cancel_modes;
+        let mut shift = 0;
+        for (date, day) in self.days.iter_mut() {
+            if let Day::Cycle(n) = *day {
+                if shift > 0 {
+                    *day = Day::Cycle((n - 1 + len - shift % len) % len + 1);
+                }
+                let cancelled = self.specials.cancelled(date);
+                if cancelled.map(|c| mode(modes, c.kind)) == Some(CancelMode::Shift) {
+                    shift += 1;
+                }
+            }
+        }
+    }
+
+    /// Every cycle day after `date` moves back one.
+    fn shift_after(&mut self, date: NaiveDate) {
+        let len = self.day_types.cycle_length();
+        for (_, day) in self.days.range_mut(date + chrono::Duration::days(1)..) {
+            if let Day::Cycle(n) = *day {
+                *day = Day::Cycle((n + len - 2) % len + 1);
+            }
+        }
+    }
+
+    // what-if edits (see whatif.rs). these only ever get done to a clone
+
+    /// Cancels a day, and shifts the rotation if that kind of cancellation does.
+    pub fn cancel(&mut self, date: NaiveDate, c: Cancelled) {
+        let kind = c.kind;
+        if !self.specials.cancel(date, c) {
+            return; // can't cancel it twice
+        }
+        if let Some(Day::Cycle(_)) = self.days.get(&date) {
+            if mode(&self.cancel_modes, kind) == CancelMode::Shift {
+                self.shift_after(date);
+            }
+        }
+    }
+
+    /// Makes a day a ped day. Ped days aren't in the rotation, so everything
+    /// after moves back one (same as the generator).
+    pub fn make_ped(&mut self, date: NaiveDate) {
+        // a cancellation that shifts already moved everything back for this day
+        let shifted = self.specials.cancelled(&date).map(|c| mode(&self.cancel_modes, c.kind)) == Some(CancelMode::Shift);
+        if let (Some(Day::Cycle(_)), false) = (self.days.get(&date), shifted) {
+            self.shift_after(date);
+        }
+        if let Some(day) = self.days.get_mut(&date) {
+            *day = Day::Ped;
+        }
+    }
+
+    /// Swaps what two days are.
+    pub fn swap_days(&mut self, a: NaiveDate, b: NaiveDate) {
+        if let (Some(x), Some(y)) = (self.day(&a), self.day(&b)) {
+            self.days.insert(a, y);
+            self.days.insert(b, x);
+        }
+    }
+
+    pub(crate) fn read_runtime(path: &str) -> Result<Vec<(u64, csv::StringRecord)>, LoadError> {
+        let reader = match csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_path(path)
+        {
+            Ok(r) => r,
+            Err(e) => match e.kind() {
+                csv::ErrorKind::Io(io) if io.kind() == ErrorKind::NotFound => return Ok(vec![]),
+                _ => return Err(Self::err(path, 0, &e.to_string())),
+            },
+        };
+        Self::records(path, reader)
+    }
+
+    fn records(path: &str, mut reader: csv::Reader<impl std::io::Read>) -> Result<Vec<(u64, csv::StringRecord)>, LoadError> {
+        let mut records = vec![];
+        for r in reader.records() {
+            match r {
+                Ok(record) => {
+                    let line = record.position().map(|p| p.line()).unwrap_or_default();
+                    records.push((line, record));
+                },
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or_default();
+                    return Err(Self::err(path, line, &e.to_string()));
+                },
+            }
+        }
+        Ok(records)
+    }
+
+    pub(crate) fn parse_date(path: &str, line: u64, s: Option<&str>) -> Result<NaiveDate, LoadError> {
+        let s = s.unwrap_or_default();
+        NaiveDate::parse_from_str(s, DATE_FMT)
+            .map_err(|_| Self::err(path, line, &format!("bad date '{}'", s)))
+    }
+
+    pub(crate) fn err(path: &str, line: u64, msg: &str) -> LoadError {
+        LoadError {
+            file: path.to_string(),
+            line,
+            msg: msg.to_string(),
+        }
+    }
+
+    /// Which grade (or cohort, whatever) this calendar is for.
+    pub fn grade(&self) -> &str {
+        &self.grade
+    }
+
+    /// The school year, like "2021-2022".
+    pub fn year(&self) -> &str {
+        &self.year
+    }
+
+    pub fn first(&self) -> Option<NaiveDate> {
+        self.days.keys().next().cloned()
+    }
+
+    pub fn last(&self) -> Option<NaiveDate> {
+        self.days.keys().next_back().cloned()
+    }
+
+    pub fn day(&self, date: &NaiveDate) -> Option<Day> {
+        self.days.get(date).cloned()
+    }
+
+    /// Every day in the calendar, in order.
+    pub fn days(&self) -> impl Iterator<Item = (&NaiveDate, &Day)> {
+        self.days.iter()
+    }
+
+    /// What the timetable says for a day, going by whichever term it's in.
+    pub fn classes(&self, date: &NaiveDate, day: &Day) -> Option<Vec<String>> {
+        match day {
+            Day::Exam => Some(vec![" ".to_string()]),
+            _ => self.timetable_on(date).get(day.cycle_index()?).cloned(),
+        }
+    }
+
+    pub fn day_types(&self) -> &DayTypes {
+        &self.day_types
+    }
+
+    /// The last term a date is in with its own timetable has the say,
+    /// otherwise it's the normal one.
+    pub fn timetable_on(&self, date: &NaiveDate) -> &[Vec<String>] {
+        self.terms
+            .iter()
+            .rev()
+            .filter(|t| t.contains(date))
+            .find_map(|t| t.timetable.as_deref())
+            .unwrap_or(&self.timetable)
+    }
+
+    /// The normal timetable, then every term's.
+    pub fn timetables(&self) -> impl Iterator<Item = &Vec<Vec<String>>> {
+        std::iter::once(&self.timetable).chain(self.terms.iter().filter_map(|t| t.timetable.as_ref()))
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// The term (or semester) a date is in.
+    pub fn term(&self, date: &NaiveDate, kind: TermKind) -> Option<&Term> {
+        self.terms.iter().find(|t| t.kind == kind && t.contains(date))
+    }
+
+    /// special.csv's, then recurring.csv's.
+    pub fn specials(&self, date: &NaiveDate) -> Vec<Special> {
+        let mut specials = self.specials.on(date).to_vec();
+        if let (Some(day), Some(first)) = (self.days.get(date), self.first()) {
+            specials.extend(
+                self.recurring_specials
+                    .iter()
+                    .filter(|(r, _)| r.on(date, day, first))
+                    .map(|(_, s)| Special { from: *date, until: *date, ..s.clone() }),
+            );
+        }
+        specials
+    }
+
+    pub fn cancelled(&self, date: &NaiveDate) -> Option<&Cancelled> {
+        self.specials.cancelled(date)
+    }
+
+    // still not used anywhere (see the comment that used to be in blocks.rs),
+    // but the api wants it
+    pub fn is_online(&self, date: &NaiveDate) -> bool {
+        self.online.contains(date)
+    }
+
+    /// The bell schedule for a date: the last BELL row in lookup.csv (or CTD
+    /// with a bell schedule) if there is one, otherwise whatever that kind of
+    /// day normally gets.
+    pub fn bells(&self, date: &NaiveDate, day: &Day) -> Option<&BellSchedule> {
+        let from_lookup = self.lookup(date).into_iter().rev().find_map(|r| match r.action {
+            Action::Bell(name) => Some(name),
+            Action::Ctd(mode) => self.ctd_modes.get(&mode).and_then(|m| m.bell.clone()),
+            _ => None,
+        });
+        match from_lookup {
+            Some(name) => self.bells.get(&name),
+            None => self.bells.for_day(day),
+        }
+    }
+
+    pub fn ctd_modes(&self) -> &CtdModes {
+        &self.ctd_modes
+    }
+
+    /// recurring.csv's rows first, so a one-off in lookup.csv wins.
+    pub fn lookup(&self, date: &NaiveDate) -> Vec<LookupRow> {
+        let mut rows: Vec<LookupRow> = match (self.days.get(date), self.first()) {
+            (Some(day), Some(first)) => self.recurring_lookup.iter().filter(|(r, _)| r.on(date, day, first)).map(|(_, row)| row.clone()).collect(),
+            _ => vec![],
+        };
+        rows.extend(self.lookup.get(date).into_iter().flatten().cloned());
+        rows
+    }
+}
+
+/// Where one calendar's days and timetable come from.
+pub struct Source {
+    pub grade: String,
+    /// what to call the files in error messages
+    pub data_name: String,
+    pub data: String,
+    pub classes_name: String,
+    pub classes: String,
+}
+
+impl Source {
+    /// The one that's baked into the binary.
+    pub fn builtin() -> Self {
+        Source {
+            grade: BUILTIN_GRADE.to_string(),
+            // the baked-in files don't have a path at runtime, so just name them
+            data_name: "src/sched_data_11.csv".to_string(),
+            data: SCHED_DATA.to_string(),
+            classes_name: "src/sched_classes.csv".to_string(),
+            classes: SCHED_CLASSES.to_string(),
+        }
+    }
+}
+
+// anything that isn't in cancel_modes.csv just skips, like it always did
+fn mode(modes: &[(Cancellation, CancelMode)], c: Cancellation) -> CancelMode {
+    modes.iter().rev().find(|(x, _)| *x == c).map(|(_, m)| *m).unwrap_or(CancelMode::Skip)
+}
+
+/// August to July, named after both years.
+fn school_year(date: &NaiveDate) -> String {
+    let start = if date.month() >= 8 {date.year()} else {date.year() - 1};
+    format!("{}-{}", start, start + 1)
+}
+
+/// Every calendar we know about: the baked-in one, and whatever calendars.csv
+/// adds (other grades, next year). They all share the runtime files.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    calendars: Vec<Calendar>,
+    // the files calendars.csv pointed at, so the watcher knows about them
+    files: Vec<String>,
+}
+
+impl Registry {
+    /// The baked-in source, then one per row of calendars.csv
+    /// (`grade,sched data file,classes file`).
+    pub fn sources() -> Result<Vec<Source>, LoadError> {
+        let mut sources = vec![Source::builtin()];
+        for (line, record) in Calendar::read_runtime(CALENDARS_PATH)? {
+            let grade = record.get(0).unwrap_or_default().trim();
+            if grade.is_empty() {
+                return Err(Calendar::err(CALENDARS_PATH, line, "no grade"));
+            }
+            let read = |path: Option<&str>| {
+                let path = path.unwrap_or_default().trim();
+                std::fs::read_to_string(path)
+                    .map(|text| (path.to_string(), text))
+                    .map_err(|e| Calendar::err(CALENDARS_PATH, line, &format!("can't read '{}' ({})", path, e)))
+            };
+            let (data_name, data) = read(record.get(1))?;
+            let (classes_name, classes) = read(record.get(2))?;
+            sources.push(Source {
+                grade: grade.to_string(),
+                data_name,
+                data,
+                classes_name,
+                classes,
+            });
+        }
+        Ok(sources)
+    }
+
+    pub fn load() -> Result<Self, LoadError> {
+        let shared = Calendar::load_shared()?;
+        let mut reg = Registry::default();
+        for source in Self::sources()? {
+            let cal = shared.with_source(&source)?;
+            if reg.calendars.iter().any(|c| c.grade == cal.grade && c.year == cal.year) {
+                return Err(Calendar::err(
+                    &source.data_name,
+                    0,
+                    &format!("there's already a grade {} calendar for {}", cal.grade, cal.year),
+                ));
+            }
+            if !reg.calendars.is_empty() {
+                reg.files.push(source.data_name);
+                reg.files.push(source.classes_name);
+            }
+            reg.files.extend(cal.terms.iter().filter_map(|t| t.classes_name.clone()));
+            reg.calendars.push(cal);
+        }
+        // grades can share a term's timetable
+        reg.files.sort();
+        reg.files.dedup();
+        reg.calendars.sort_by_key(|c| (c.grade.clone(), c.first()));
+        Ok(reg)
+    }
+
+    pub fn calendars(&self) -> &[Calendar] {
+        &self.calendars
+    }
+
+    /// Every grade there's at least one calendar for.
+    pub fn grades(&self) -> Vec<&str> {
+        let mut grades: Vec<&str> = self.calendars.iter().map(|c| c.grade()).collect();
+        grades.dedup(); // already sorted by grade
+        grades
+    }
+
+    pub fn knows(&self, grade: &str) -> bool {
+        self.calendars.iter().any(|c| c.grade == grade)
+    }
+
+    /// A grade we don't have (or no grade) means the baked-in one.
+    pub fn grade<'a>(&self, grade: Option<&'a str>) -> &'a str {
+        match grade {
+            Some(g) if self.calendars.iter().any(|c| c.grade == g) => g,
+            _ => BUILTIN_GRADE,
+        }
+    }
+
+    /// All of one grade's calendars, oldest first.
+    pub fn for_grade(&self, grade: Option<&str>) -> Vec<&Calendar> {
+        let grade = self.grade(grade);
+        self.calendars.iter().filter(|c| c.grade == grade).collect()
+    }
+
+    /// The calendar a grade is on for some date: the one with that date in it,
+    /// or else the last one that already started (summer), or else the first.
+    pub fn select(&self, grade: Option<&str>, date: NaiveDate) -> &Calendar {
+        let cals = self.for_grade(grade);
+        cals.iter()
+            .find(|c| c.days.contains_key(&date))
+            .or_else(|| cals.iter().rev().find(|c| c.first().map(|f| f <= date).unwrap_or(false)))
+            .or_else(|| cals.first())
+            .expect("the baked-in calendar is always there")
+    }
+}
+
+/// Holds the current calendars. Requests grab an `Arc` of whatever snapshot is
+/// current and use that the whole way through, so a reload mid-request can't
+/// give anyone half of the old data and half of the new.
+#[derive(Clone)]
+pub struct CalendarStore {
+    current: Arc<RwLock<Arc<Registry>>>,
+}
+
+impl CalendarStore {
+    pub fn new(reg: Registry) -> Self {
+        CalendarStore {
+            current: Arc::new(RwLock::new(Arc::new(reg))),
+        }
+    }
+
+    pub fn get(&self) -> Arc<Registry> {
+        self.current.read().unwrap().clone()
+    }
+
+    fn swap(&self, reg: Registry) {
+        *self.current.write().unwrap() = Arc::new(reg);
+    }
+
+    /// Polls the runtime files and reloads when any of them change. If the new
+    /// set doesn't load, the old snapshot stays and we complain about it.
+    pub fn watch(&self) {
+        let store = self.clone();
+        thread::spawn(move || {
+            let mut last = Self::stamps(&store.get().files);
+            loop {
+                thread::sleep(WATCH_INTERVAL);
+                let stamps = Self::stamps(&store.get().files);
+                if stamps == last {
+                    continue;
+                }
+                last = stamps;
+
+                match Registry::load() {
+                    Ok(reg) => {
+                        store.swap(reg);
+                        println!("calendar: reloaded runtime files");
+                    },
+                    Err(e) => eprintln!("calendar: not reloading, keeping the old data ({})", e),
+                }
+            }
+        });
+    }
+
+    fn stamps(files: &[String]) -> Vec<Option<SystemTime>> {
+        [DAY_TYPES_PATH, CALENDARS_PATH, CANCEL_MODES_PATH, SPECIALS_PATH, ONLINE_PATH, LOOKUP_PATH, RECURRING_PATH, BELLS_PATH, BELL_DAYS_PATH, CTD_MODES_PATH, TERMS_PATH]
+            .iter()
+            .copied()
+            .chain(files.iter().map(|f| f.as_str()))
+            .map(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
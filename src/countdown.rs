@@ -0,0 +1,87 @@
+// Countdown
+// how long until the next day off (or exam, or the end). for the stats
+// section and for the phone widget people
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use crate::calendar::Calendar;
+use crate::days::{Counts, Day};
+use crate::markup;
+use crate::profile::Profile;
+use crate::schedule::{DayStatus, ScheduleDay};
+
+#[derive(Serialize, Debug)]
+pub struct Countdown {
+    /// holiday, ped, exam, last_day or special. for the widgets to switch on
+    pub kind: &'static str,
+    /// "Next holiday"
+    pub label: String,
+    /// the special on that day, if there is one ("Winter break")
+    pub what: Option<String>,
+    pub date: NaiveDate,
+    /// calendar days, 0 is today
+    pub days: i64,
+    /// school days left before it. today counts until school's out
+    pub school_days: usize,
+}
+
+/// Counts as a school day in the stats, and classes are actually happening.
+fn is_school_day(cal: &Calendar, date: &NaiveDate, day: &Day) -> bool {
+    cal.day_types().counts(day) == Counts::School && cal.cancelled(date).is_none()
+}
+
+/// Everything coming up in this calendar, soonest first. Whatever's already
+/// passed (or isn't in the calendar at all) just isn't there.
+pub fn countdowns(cal: &Calendar, now: NaiveDateTime) -> Vec<Countdown> {
+    let today = now.date();
+    let upcoming: Vec<(&NaiveDate, &Day)> = cal.days().filter(|(d, _)| **d >= today).collect();
+
+    let today_over = ScheduleDay::build(cal, today, now, &Profile::default()).status == DayStatus::IsOver;
+    let done = |d: &NaiveDate| *d == today && today_over;
+    let school_days_before = |date: &NaiveDate| {
+        upcoming
+            .iter()
+            .filter(|(d, day)| *d < date && is_school_day(cal, d, day))
+            .filter(|(d, _)| !done(d))
+            .count()
+    };
+    let first_special = |date: &NaiveDate| cal.specials(date).first().map(|s| markup::to_plain(&s.text));
+
+    let find = |kind: &'static str, label: &str, is: &dyn Fn(&NaiveDate, &Day) -> bool| {
+        upcoming.iter().find(|(d, day)| is(d, day)).map(|(d, _)| Countdown {
+            kind,
+            label: label.to_string(),
+            what: first_special(d),
+            date: **d,
+            days: (**d - today).num_days(),
+            school_days: school_days_before(d),
+        })
+    };
+
+    let mut countdowns: Vec<Countdown> = vec![
+        find("holiday", "Next holiday", &|_, day| matches!(day, Day::Holiday | Day::HolidayDontCount)),
+        find("ped", "Next ped day", &|_, day| *day == Day::Ped),
+        find("exam", "Next exam day", &|d, day| *day == Day::Exam && cal.cancelled(d).is_none()),
+        find("special", "Next special", &|d, _| !cal.specials(d).is_empty() && !done(d)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    // the last one, not the next one
+    if let Some((d, _)) = upcoming.iter().rev().find(|(d, day)| is_school_day(cal, d, day)) {
+        if !done(d) {
+            countdowns.push(Countdown {
+                kind: "last_day",
+                label: "Last day of classes".to_string(),
+                what: None,
+                date: **d,
+                days: (**d - today).num_days(),
+                school_days: school_days_before(d),
+            });
+        }
+    }
+
+    countdowns.sort_by_key(|c| c.date);
+    countdowns
+}
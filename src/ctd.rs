@@ -0,0 +1,117 @@
+// CTD
+// "change the day" modes: production week, show days, the fashion show.
+// they used to be a match in apply_lookup, so every new drama thing meant a
+// redeploy. now they're rows in ctd_modes.csv:
+//   mode,label,a Show!          what the day gets called
+//   mode,color,#cb762d          the block's color
+//   mode,keep,Instro@1-4        classes that survive, the rest get blanked
+//   mode,blank,3                classes that get blanked (after the keeps)
+//   mode,bell,assembly          a bell schedule for the day (from bells.csv)
+// keep and blank take a class from the timetable (Chant), a period (3), either
+// one only in some periods (Instro@1-4), or * for everything. no keep rows
+// means everything's kept
+
+use std::collections::BTreeMap;
+
+use crate::bells::Bells;
+use crate::days::check_color;
+
+pub const CTD_MODES_PATH: &str = "./ctd_modes.csv";
+
+#[derive(Clone, Debug)]
+struct Match {
+    class: Option<String>,
+    // 1-based, inclusive
+    periods: Option<(usize, usize)>,
+}
+
+impl Match {
+    fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s == "*" {
+            return Ok(Match { class: None, periods: None });
+        }
+        let range = |r: &str| {
+            let bad = || format!("'{}' isn't a period or a range like 1-4", r);
+            let (a, b) = r.split_once('-').unwrap_or((r, r));
+            match (a.trim().parse::<usize>(), b.trim().parse::<usize>()) {
+                (Ok(a), Ok(b)) if a >= 1 && a <= b => Ok((a, b)),
+                _ => Err(bad()),
+            }
+        };
+        match s.split_once('@') {
+            Some((class, r)) => Ok(Match { class: Some(class.trim().to_string()), periods: Some(range(r)?) }),
+            None if s.chars().all(|c| c.is_ascii_digit() || c == '-') => Ok(Match { class: None, periods: Some(range(s)?) }),
+            None => Ok(Match { class: Some(s.to_string()), periods: None }),
+        }
+    }
+
+    /// `period` is 1-based, `class` is what the timetable says.
+    fn hits(&self, period: usize, class: &str) -> bool {
+        self.class.as_ref().map(|c| c == class).unwrap_or(true)
+            && self.periods.map(|(a, b)| (a..=b).contains(&period)).unwrap_or(true)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CtdMode {
+    pub name: String,
+    pub label: String,
+    pub color: String,
+    pub bell: Option<String>,
+    keep: Vec<Match>,
+    blank: Vec<Match>,
+}
+
+impl CtdMode {
+    /// Whether period `period` (1-based), normally `class`, still happens.
+    pub fn keeps(&self, period: usize, class: &str) -> bool {
+        (self.keep.is_empty() || self.keep.iter().any(|m| m.hits(period, class)))
+            && !self.blank.iter().any(|m| m.hits(period, class))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CtdModes {
+    modes: BTreeMap<String, CtdMode>,
+}
+
+impl CtdModes {
+    /// Adds one row of ctd_modes.csv. Do this after the bells.
+    pub fn add(&mut self, record: &csv::StringRecord, bells: &Bells) -> Result<(), (usize, String)> {
+        let name = record.get(0).unwrap_or_default().trim();
+        if name.is_empty() {
+            return Err((1, "no mode name".to_string()));
+        }
+        let value = record.get(2).unwrap_or_default().trim();
+        let mode = self.modes.entry(name.to_string()).or_insert_with(|| CtdMode {
+            name: name.to_string(),
+            label: name.to_string(),
+            color: "#2b3032".to_string(),
+            bell: None,
+            keep: vec![],
+            blank: vec![],
+        });
+        match record.get(1).unwrap_or_default().trim() {
+            "label" => mode.label = value.to_string(),
+            "color" => {
+                check_color(value).map_err(|e| (3, e))?;
+                mode.color = value.to_string();
+            },
+            "keep" => mode.keep.push(Match::parse(value).map_err(|e| (3, e))?),
+            "blank" => mode.blank.push(Match::parse(value).map_err(|e| (3, e))?),
+            "bell" => {
+                if bells.get(value).is_none() {
+                    return Err((3, format!("no bell schedule called '{}'", value)));
+                }
+                mode.bell = Some(value.to_string());
+            },
+            what => return Err((2, format!("'{}' isn't label, color, keep, blank or bell", what))),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CtdMode> {
+        self.modes.get(name)
+    }
+}
@@ -0,0 +1,187 @@
+// Days
+// what kinds of day there are. the codes in the calendar csv, their names and
+// colors, and how long the cycle is all come from day_types.csv, so next
+// year's 6 (or 10, or whatever) day rotation doesn't need a code change
+
+use serde::{Deserialize, Serialize};
+
+pub const DAY_TYPES_PATH: &str = "./day_types.csv";
+
+/// Colors in the csv files are #rgb or #rrggbb.
+pub fn check_color(color: &str) -> Result<(), String> {
+    if color.starts_with('#') && (color.len() == 4 || color.len() == 7) {
+        Ok(())
+    } else {
+        Err(format!("'{}' isn't a color like #2b3032", color))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Day {
+    /// a day in the rotation, numbered from 1
+    Cycle(usize),
+    Ped,
+    Holiday,
+    HolidayDontCount,
+    Weekend,
+    Exam,
+    NoExam,
+    /// on the calendar, but nothing's happening (before the year starts etc)
+    NoSchool,
+    Unknown,
+}
+
+impl Day {
+    /// Which row of the timetable this day uses, if it's a cycle day at all.
+    pub fn cycle_index(&self) -> Option<usize> {
+        match self {
+            Day::Cycle(n) => Some(n - 1),
+            _ => None,
+        }
+    }
+}
+
+/// What a day counts as in the stats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counts {
+    /// a day of classes
+    School,
+    Exam,
+    /// a ped day or holiday in the middle of the year
+    Off,
+    /// weekends, summer, whatever "D" is
+    Nothing,
+}
+
+impl Counts {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "school" => Some(Counts::School),
+            "exam" => Some(Counts::Exam),
+            "off" => Some(Counts::Off),
+            "nothing" => Some(Counts::Nothing),
+            _ => None,
+        }
+    }
+
+    /// What a kind of day counts as when day_types.csv doesn't say.
+    fn default_for(day: &Day) -> Self {
+        match day {
+            Day::Cycle(_) => Counts::School,
+            Day::Exam => Counts::Exam,
+            Day::Ped | Day::Holiday => Counts::Off,
+            _ => Counts::Nothing,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DayType {
+    pub code: String,
+    pub day: Day,
+    pub name: String,
+    pub color: String,
+    pub counts: Counts,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DayTypes {
+    types: Vec<DayType>,
+}
+
+impl DayTypes {
+    /// Adds one row of day_types.csv (`code,kind,name,color,counts`). Cycle
+    /// days get numbered in the order they show up.
+    pub fn add(&mut self, record: &csv::StringRecord) -> Result<(), (usize, String)> {
+        let code = record.get(0).unwrap_or_default().trim();
+        if code.is_empty() {
+            return Err((1, "no code".to_string()));
+        }
+        if self.types.iter().any(|t| t.code == code) {
+            return Err((1, format!("'{}' is already a day type", code)));
+        }
+        let day = match record.get(1).unwrap_or_default().trim() {
+            "cycle" => Day::Cycle(self.cycle_length() + 1),
+            "ped" => Day::Ped,
+            "holiday" => Day::Holiday,
+            "holiday-dont-count" => Day::HolidayDontCount,
+            "weekend" => Day::Weekend,
+            "exam" => Day::Exam,
+            "no-exam" => Day::NoExam,
+            "none" => Day::NoSchool,
+            k => return Err((2, format!("'{}' isn't a kind of day", k))),
+        };
+        // the calendar only keeps the kind, so two rows of the same kind
+        // would both end up with the first one's name and color
+        if let Some(t) = self.types.iter().find(|t| t.day == day) {
+            return Err((2, format!("'{}' is already this kind of day, there can only be one", t.code)));
+        }
+        let color = record.get(3).unwrap_or_default().trim();
+        check_color(color).map_err(|e| (4, e))?;
+
+        let counts = match record.get(4).unwrap_or_default().trim() {
+            "" => Counts::default_for(&day),
+            c => Counts::parse(c).ok_or((5, format!("'{}' isn't school, exam, off or nothing", c)))?,
+        };
+
+        self.types.push(DayType {
+            code: code.to_string(),
+            day,
+            name: record.get(2).unwrap_or_default().to_string(),
+            color: color.to_string(),
+            counts,
+        });
+        Ok(())
+    }
+
+    /// Every one, in file order.
+    pub fn all(&self) -> &[DayType] {
+        &self.types
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    pub fn cycle_length(&self) -> usize {
+        self.types.iter().filter(|t| t.day.cycle_index().is_some()).count()
+    }
+
+    /// Unknown codes are Day::Unknown, which is bright red on purpose.
+    pub fn parse(&self, code: &str) -> Day {
+        match self.types.iter().find(|t| t.code == code.trim()) {
+            Some(t) => t.day.clone(),
+            None => Day::Unknown,
+        }
+    }
+
+    pub fn counts(&self, day: &Day) -> Counts {
+        self.get(day).map(|t| t.counts).unwrap_or(Counts::Nothing)
+    }
+
+    /// The code for a kind of day.
+    pub fn code(&self, day: &Day) -> Option<&str> {
+        self.get(day).map(|t| t.code.as_str())
+    }
+
+    fn get(&self, day: &Day) -> Option<&DayType> {
+        self.types.iter().find(|t| t.day == *day)
+    }
+
+    pub fn name(&self, day: &Option<Day>) -> String {
+        match day {
+            Some(d) => match self.get(d) {
+                Some(t) => t.name.clone(),
+                None => "unknown ???".to_string(),
+            },
+            None => "no day".to_string(),
+        }
+    }
+
+    pub fn color(&self, day: &Day) -> String {
+        match self.get(day) {
+            Some(t) => t.color.clone(),
+            None => "#FF0000".to_string(), // should never see this
+        }
+    }
+}
@@ -0,0 +1,201 @@
+// Generate
+// works out the whole date -> day list from a few rules instead of typing
+// 300 lines by hand, and diffs it against the file we already have (so we
+// can check the school's calendar against ours)
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use crate::calendar::{Calendar, LoadError, DATE_FMT};
+use crate::days::{Day, DayTypes};
+
+/// Everything in a rules file.
+///
+/// ```text
+/// FIRST,30-08-2021,6          first day of school, and which cycle day it is
+/// FROM,27-08-2021             where the calendar starts (FIRST if there's none)
+/// TO,21-06-2022               and ends
+/// WEEKEND,W,Sat,Sun           the weekend code, and which days it's on
+/// OFF,24-09-2021,P            not in the rotation (ped days, holidays, exams...)
+/// OFF,20-12-2021,31-12-2021,D the same for a whole range, weekends included
+/// REPEAT,17-09-2021,5         a cycle day that doesn't move the rotation along
+/// RESTART,27-09-2021,6        the rotation picks up from this day instead
+/// ```
+#[derive(Debug)]
+pub struct Rules {
+    from: NaiveDate,
+    to: NaiveDate,
+    first: NaiveDate,
+    first_day: usize,
+    weekend_code: String,
+    weekend: Vec<Weekday>,
+    off: BTreeMap<NaiveDate, String>,
+    repeat: BTreeMap<NaiveDate, usize>,
+    restart: BTreeMap<NaiveDate, usize>,
+}
+
+impl Rules {
+    pub fn load(path: &str, day_types: &DayTypes) -> Result<Self, LoadError> {
+        let mut from = None;
+        let mut to = None;
+        let mut first = None;
+        let mut weekend = None;
+        let mut off = BTreeMap::new();
+        let mut repeat = BTreeMap::new();
+        let mut restart = BTreeMap::new();
+
+        let cycle_length = day_types.cycle_length();
+        let cycle_day = |line, s: Option<&str>| match s.unwrap_or_default().trim().parse::<usize>() {
+            Ok(n) if n >= 1 && n <= cycle_length => Ok(n),
+            _ => Err(Calendar::err(path, line, &format!("'{}' isn't a day of the {} day cycle", s.unwrap_or_default(), cycle_length))),
+        };
+        // off days and the weekend have to be real codes, but not cycle days
+        let off_code = |line, s: Option<&str>| {
+            let code = s.unwrap_or_default().trim();
+            match day_types.parse(code) {
+                Day::Unknown => Err(Calendar::err(path, line, &format!("unknown day code '{}'", code))),
+                Day::Cycle(_) => Err(Calendar::err(path, line, &format!("'{}' is a cycle day, use REPEAT or RESTART", code))),
+                _ => Ok(code.to_string()),
+            }
+        };
+
+        let rows = Calendar::read_runtime(path)?;
+        if rows.is_empty() {
+            return Err(Calendar::err(path, 0, "no rules, is the file missing?"));
+        }
+        for (line, record) in rows {
+            let date = |i| Calendar::parse_date(path, line, record.get(i));
+            match record.get(0).unwrap_or_default() {
+                "FIRST" => first = Some((date(1)?, cycle_day(line, record.get(2))?)),
+                "FROM" => from = Some(date(1)?),
+                "TO" => to = Some(date(1)?),
+                "WEEKEND" => {
+                    let code = off_code(line, record.get(1))?;
+                    let days = record
+                        .iter()
+                        .skip(2)
+                        .map(|d| d.trim().parse::<Weekday>().map_err(|_| Calendar::err(path, line, &format!("'{}' isn't a day of the week", d))))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    weekend = Some((code, days));
+                },
+                "OFF" => {
+                    // OFF,date,code or OFF,from,to,code
+                    let (start, end, code) = if record.len() > 3 {
+                        (date(1)?, date(2)?, off_code(line, record.get(3))?)
+                    } else {
+                        (date(1)?, date(1)?, off_code(line, record.get(2))?)
+                    };
+                    if end < start {
+                        return Err(Calendar::err(path, line, "ends before it starts"));
+                    }
+                    let mut d = start;
+                    while d <= end {
+                        off.insert(d, code.clone());
+                        d += Duration::days(1);
+                    }
+                },
+                "REPEAT" => {
+                    repeat.insert(date(1)?, cycle_day(line, record.get(2))?);
+                },
+                "RESTART" => {
+                    restart.insert(date(1)?, cycle_day(line, record.get(2))?);
+                },
+                what => return Err(Calendar::err(path, line, &format!("'{}' isn't FIRST, FROM, TO, WEEKEND, OFF, REPEAT or RESTART", what))),
+            }
+        }
+
+        let (first, first_day) = first.ok_or_else(|| Calendar::err(path, 0, "no FIRST day"))?;
+        let to = to.ok_or_else(|| Calendar::err(path, 0, "no TO, when does it end?"))?;
+        let from = from.unwrap_or(first);
+        if first < from || to < first {
+            return Err(Calendar::err(path, 0, "FIRST has to be between FROM and TO"));
+        }
+        // one thing per day
+        let mut taken: Vec<&NaiveDate> = off.keys().chain(repeat.keys()).chain(restart.keys()).collect();
+        taken.sort();
+        if let Some(d) = taken.windows(2).find(|w| w[0] == w[1]) {
+            return Err(Calendar::err(path, 0, &format!("{} has more than one of OFF, REPEAT and RESTART", d[0].format(DATE_FMT))));
+        }
+        let (weekend_code, weekend) = weekend.ok_or_else(|| Calendar::err(path, 0, "no WEEKEND"))?;
+
+        Ok(Rules {
+            from,
+            to,
+            first,
+            first_day,
+            weekend_code,
+            weekend,
+            off,
+            repeat,
+            restart,
+        })
+    }
+
+    /// Every date from FROM to TO with its day code, same as the sched data csv.
+    pub fn generate(&self, day_types: &DayTypes) -> Result<Vec<(NaiveDate, String)>, String> {
+        let cycle_length = day_types.cycle_length();
+        let cycle_code = |n| day_types.code(&Day::Cycle(n)).unwrap_or_default().to_string();
+
+        let mut days = vec![];
+        let mut next = self.first_day;
+        let mut date = self.from;
+        while date <= self.to {
+            let code = if let Some(code) = self.off.get(&date) {
+                code.clone()
+            } else if let Some(n) = self.repeat.get(&date) {
+                cycle_code(*n)
+            } else if let Some(n) = self.restart.get(&date) {
+                next = n % cycle_length + 1;
+                cycle_code(*n)
+            } else if self.weekend.contains(&date.weekday()) {
+                self.weekend_code.clone()
+            } else if date < self.first {
+                return Err(format!("{} is before the FIRST day but isn't OFF", date.format(DATE_FMT)));
+            } else {
+                let code = cycle_code(next);
+                next = next % cycle_length + 1;
+                code
+            };
+            days.push((date, code));
+            date += Duration::days(1);
+        }
+        Ok(days)
+    }
+}
+
+/// Every difference between a generated calendar and a sched data csv, as
+/// `file:line: problem`.
+pub fn diff(generated: &[(NaiveDate, String)], name: &str, existing: &str) -> Vec<String> {
+    let mut theirs: BTreeMap<NaiveDate, (u64, String)> = BTreeMap::new();
+    let mut out = vec![];
+    let mut reader = csv::ReaderBuilder::new().has_headers(false).flexible(true).from_reader(existing.as_bytes());
+    for r in reader.records() {
+        match r {
+            Ok(record) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                let s = record.get(0).unwrap_or_default();
+                match NaiveDate::parse_from_str(s, DATE_FMT) {
+                    Ok(d) => {
+                        theirs.insert(d, (line, record.get(1).unwrap_or_default().trim().to_string()));
+                    },
+                    Err(_) => out.push(format!("{}:{}: bad date '{}'", name, line, s)),
+                }
+            },
+            Err(e) => out.push(format!("{}:{}: {}", name, e.position().map(|p| p.line()).unwrap_or_default(), e)),
+        }
+    }
+
+    for (date, code) in generated {
+        match theirs.remove(date) {
+            Some((line, c)) if c != *code => {
+                out.push(format!("{}:{}: {} is {} here but the rules make it {}", name, line, date.format(DATE_FMT), c, code));
+            },
+            Some(_) => {},
+            None => out.push(format!("{}: {} is missing (the rules make it {})", name, date.format(DATE_FMT), code)),
+        }
+    }
+    for (date, (line, _)) in theirs {
+        out.push(format!("{}:{}: {} isn't in the rules' calendar at all", name, line, date.format(DATE_FMT)));
+    }
+    out
+}
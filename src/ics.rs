@@ -0,0 +1,156 @@
+// iCalendar
+// the whole schedule as an .ics (rfc 5545) so phones can subscribe to it
+// instead of people typing the rotation in by hand
+
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use crate::days::Day;
+use crate::calendar::Calendar;
+use crate::markup;
+use crate::profile::Profile;
+use crate::schedule::{Cancellation, ScheduleDay};
+
+struct Event {
+    uid: String,
+    summary: String,
+    location: Option<String>,
+    start: Start,
+}
+
+enum Start {
+    AllDay(NaiveDate),
+    // floating local time, same as the school's
+    Timed(NaiveDateTime, NaiveDateTime),
+}
+
+/// Every day in the calendars (one grade's years, so a subscription keeps
+/// working in september) as one VCALENDAR.
+pub fn generate(cals: &[&Calendar], now: NaiveDateTime, code: &str) -> String {
+    let mut events: Vec<Event> = vec![];
+    for cal in cals {
+        let profile = Profile::decode(cal, code);
+        for (date, _) in cal.days() {
+            events.extend(day_events(&ScheduleDay::build(cal, *date, now, &profile), cal.grade()));
+        }
+    }
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//xytk4//sched//EN");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, "X-WR-CALNAME:School schedule");
+    for e in events {
+        line(&mut out, "BEGIN:VEVENT");
+        line(&mut out, &format!("UID:{}", e.uid));
+        line(&mut out, &format!("DTSTAMP:{}", stamp));
+        match e.start {
+            Start::AllDay(d) => {
+                line(&mut out, &format!("DTSTART;VALUE=DATE:{}", d.format("%Y%m%d")));
+                line(&mut out, &format!("DTEND;VALUE=DATE:{}", (d + Duration::days(1)).format("%Y%m%d")));
+                line(&mut out, "TRANSP:TRANSPARENT");
+            },
+            Start::Timed(s, e) => {
+                line(&mut out, &format!("DTSTART:{}", s.format("%Y%m%dT%H%M%S")));
+                line(&mut out, &format!("DTEND:{}", e.format("%Y%m%dT%H%M%S")));
+            },
+        }
+        line(&mut out, &format!("SUMMARY:{}", escape(&e.summary)));
+        if let Some(l) = &e.location {
+            line(&mut out, &format!("LOCATION:{}", escape(l)));
+        }
+        line(&mut out, "END:VEVENT");
+    }
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+// the grade's in every uid, so a second grade's feed doesn't overwrite the first
+fn day_events(sd: &ScheduleDay, grade: &str) -> Vec<Event> {
+    let ymd = sd.date.format("%Y%m%d");
+    let mut events = vec![];
+    let all_day = |what: &str, summary: String| Event {
+        uid: format!("{}-{}-g{}@sched", ymd, what, grade),
+        summary,
+        location: None,
+        start: Start::AllDay(sd.date),
+    };
+
+    // weekends are just weekends
+    if let Some(Day::Weekend) | Some(Day::NoSchool) | None = sd.day {
+        return events;
+    }
+
+    match &sd.cancelled {
+        Some(c) => {
+            let what = match c.kind {
+                Cancellation::Cancelled => "Cancelled",
+                Cancellation::SnowDay => "Snow day!",
+            };
+            let summary = match &c.reason {
+                Some(r) => format!("{} ({})", what, r),
+                None => what.to_string(),
+            };
+            events.push(all_day("day", summary));
+        },
+        None => events.push(all_day("day", sd.label.clone())),
+    }
+
+    for (i, special) in sd.specials.iter().enumerate() {
+        let what = format!("special-{}", i);
+        let summary = match &special.audience {
+            Some(a) => format!("{} ({})", markup::to_plain(&special.text), a),
+            None => markup::to_plain(&special.text),
+        };
+        // just a start time is a point in time (a deadline, say)
+        let start = match (special.start, special.end) {
+            (Some(s), e) => Start::Timed(sd.date.and_time(s), sd.date.and_time(e.unwrap_or(s))),
+            (None, _) => Start::AllDay(sd.date),
+        };
+        events.push(Event {
+            uid: format!("{}-{}-g{}@sched", ymd, what, grade),
+            summary,
+            location: special.location.clone(),
+            start,
+        });
+    }
+
+    for (i, class) in sd.classes.iter().flatten().enumerate() {
+        // blank (CTD'd away, or the exam day placeholder) means nothing to put in a calendar
+        if class.name.trim().is_empty() {
+            continue;
+        }
+        if let (Some(s), Some(e)) = (class.start, class.end) {
+            events.push(Event {
+                uid: format!("{}-p{}-g{}@sched", ymd, i + 1, grade),
+                summary: class.name.clone(),
+                location: class.note.clone(),
+                start: Start::Timed(sd.date.and_time(s), sd.date.and_time(e)),
+            });
+        }
+    }
+
+    events
+}
+
+/// Text values can't have raw commas, semicolons or newlines.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Adds a content line, folded at 75 octets like the rfc wants.
+fn line(out: &mut String, s: &str) {
+    let mut len = 0;
+    for c in s.chars() {
+        if len + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            len = 1;
+        }
+        out.push(c);
+        len += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,267 @@
+// Lookup
+// lookup.csv's override language: what happens to one day's classes.
+//   date,3,Drama                   period 3 is Drama today (the old way)
+//   date,REPLACE,Drama,Rehearsal   every Drama is Rehearsal (or REPLACE,3,...)
+//   date,SWAP,2,5                  periods 2 and 5 trade places (or class names)
+//   date,DELETE,Drama              nothing in that period today
+//   date,NOTE,Math,room 204        a note next to the class
+//   date,INSERT,4,Assembly         an extra period 4, everything after moves down
+//   date,CTD,mode / date,BELL,name change the day (ctd_modes.csv) / the bells
+// they always go in the same order, whatever order the file's in: CTD, SWAP,
+// REPLACE, DELETE, NOTE and INSERT last. a class name matches what the
+// timetable says or what you picked for a split class. two rows fighting over
+// the same period is a conflict: the later one wins, and validate says so
+
+use crate::bells::Bells;
+use crate::calendar::{LOOKUP_PATH, RECURRING_PATH};
+use crate::ctd::{CtdMode, CtdModes};
+use crate::schedule::ClassSlot;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    Period(usize),
+    Class(String),
+}
+
+impl Target {
+    fn parse(s: &str) -> Self {
+        match s.trim().parse::<usize>() {
+            Ok(p) => Target::Period(p),
+            Err(_) => Target::Class(s.trim().to_string()),
+        }
+    }
+
+    /// The (0-based) periods it means today.
+    fn resolve(&self, classes: &[ClassSlot]) -> Vec<usize> {
+        match self {
+            Target::Period(p) if (1..=classes.len()).contains(p) => vec![p - 1],
+            Target::Period(_) => vec![],
+            Target::Class(name) => (0..classes.len())
+                .filter(|&i| classes[i].timetable == *name || classes[i].name == *name)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Action {
+    Ctd(String),
+    Bell(String),
+    Swap(Target, Target),
+    Replace(Target, String),
+    Delete(Target),
+    Note(Target, String),
+    Insert(usize, String),
+}
+
+impl Action {
+    // the order they get done in
+    fn step(&self) -> usize {
+        match self {
+            Action::Ctd(_) | Action::Bell(_) => 0,
+            Action::Swap(..) => 1,
+            Action::Replace(..) => 2,
+            Action::Delete(_) => 3,
+            Action::Note(..) => 4,
+            Action::Insert(..) => 5,
+        }
+    }
+}
+
+/// One row of lookup.csv (or the lookup half of a recurring.csv row), minus the date.
+#[derive(Clone, Debug)]
+pub struct LookupRow {
+    pub action: Action,
+    /// where it came from, for conflicts
+    pub file: &'static str,
+    pub line: u64,
+}
+
+impl LookupRow {
+    /// The columns from `record[at]` on. Bell schedules and CTD modes have
+    /// to exist. Errors have the column.
+    pub fn parse(record: &csv::StringRecord, at: usize, bells: &Bells, ctd_modes: &CtdModes, file: &'static str, line: u64) -> Result<Self, (usize, String)> {
+        let get = |i: usize| record.get(at + i).unwrap_or_default();
+        let column = |i: usize| at + i + 1;
+        let what = get(0).trim();
+        let period = |i: usize| match get(i).trim().parse::<usize>() {
+            Ok(p) if p >= 1 => Ok(p),
+            _ => Err((column(i), format!("'{}' isn't a period number", get(i)))),
+        };
+        let target = |i: usize| match Target::parse(get(i)) {
+            Target::Class(c) if c.is_empty() => Err((column(i), format!("{} needs a period or a class", what))),
+            Target::Period(0) => Err((column(i), "periods start at 1".to_string())),
+            t => Ok(t),
+        };
+
+        let action = match what {
+            "BELL" => {
+                if bells.get(get(1)).is_none() {
+                    return Err((column(1), format!("no bell schedule called '{}'", get(1))));
+                }
+                Action::Bell(get(1).to_string())
+            },
+            "CTD" => {
+                if ctd_modes.get(get(1)).is_none() {
+                    return Err((column(1), format!("no CTD mode called '{}' in ctd_modes.csv", get(1))));
+                }
+                Action::Ctd(get(1).to_string())
+            },
+            "REPLACE" => Action::Replace(target(1)?, get(2).to_string()),
+            "SWAP" => Action::Swap(target(1)?, target(2)?),
+            "DELETE" => Action::Delete(target(1)?),
+            "NOTE" => Action::Note(target(1)?, get(2).to_string()),
+            "INSERT" => Action::Insert(period(1)?, get(2).to_string()),
+            _ => Action::Replace(Target::Period(period(0).map_err(|_| {
+                (column(0), format!("'{}' isn't a period number, REPLACE, SWAP, DELETE, NOTE, INSERT, CTD or BELL", what))
+            })?), get(1).to_string()),
+        };
+        Ok(LookupRow { action, file, line })
+    }
+
+    /// `lookup.csv:12`
+    pub fn place(&self) -> String {
+        format!("{}:{}", self.file.trim_start_matches("./"), self.line)
+    }
+}
+
+/// Something wrong with one day's rows, on the row that lost.
+#[derive(Debug)]
+pub struct Conflict {
+    pub file: &'static str,
+    pub line: u64,
+    pub msg: String,
+}
+
+/// Does a day's rows to its classes (if it has any). Gives back the CTD mode
+/// (the last one, if there's more than one) and every conflict.
+pub fn apply<'a>(rows: &[LookupRow], classes: &mut Option<Vec<ClassSlot>>, ctd_modes: &'a CtdModes) -> (Option<&'a CtdMode>, Vec<Conflict>) {
+    let mut rows: Vec<&LookupRow> = rows.iter().collect();
+    rows.sort_by_key(|r| r.action.step()); // stable, so file order within a step
+
+    let mut ctd = None;
+    let mut conflicts = vec![];
+    // who's done what to each period, for spotting conflicts
+    let mut touched: Vec<(usize, &LookupRow)> = vec![];
+    let mut inserted: Vec<(usize, &LookupRow)> = vec![];
+
+    for row in rows {
+        let cs = match classes.as_mut() {
+            Some(cs) => cs,
+            None => {
+                if let Action::Ctd(name) = &row.action {
+                    ctd = ctd_modes.get(name);
+                }
+                continue;
+            },
+        };
+        let conflict = |msg: String| Conflict { file: row.file, line: row.line, msg };
+        let mut touch = |i: usize, conflicts: &mut Vec<Conflict>| {
+            // a one-off in lookup.csv beats a recurring one, that's not a conflict
+            let beats = |other: &LookupRow| other.file == RECURRING_PATH && row.file == LOOKUP_PATH;
+            if let Some((_, other)) = touched.iter().find(|(j, other)| *j == i && !beats(other)) {
+                conflicts.push(conflict(format!("{} already changes period {}", other.place(), i + 1)));
+            }
+            touched.push((i, row));
+        };
+        // recurring ones don't have to find something every time
+        let nothing = |t: &Target, conflicts: &mut Vec<Conflict>| {
+            if row.file == RECURRING_PATH {
+                return;
+            }
+            conflicts.push(conflict(format!("nothing matches {}", match t {
+                Target::Period(p) => format!("period {}", p),
+                Target::Class(c) => format!("'{}'", c),
+            })));
+        };
+
+        match &row.action {
+            Action::Bell(_) => {}, // Calendar::bells does these
+            Action::Ctd(name) => {
+                let mode = match ctd_modes.get(name) {
+                    Some(m) => m,
+                    None => continue,
+                };
+                // which classes survive (going by what the timetable said)
+                for (i, slot) in cs.iter_mut().enumerate() {
+                    if !mode.keeps(i + 1, &slot.timetable) {
+                        slot.name = " ".to_string();
+                        slot.substituted = false;
+                        slot.blanked = true;
+                    }
+                }
+                ctd = Some(mode);
+            },
+            Action::Swap(a, b) => {
+                match (a.resolve(cs).as_slice(), b.resolve(cs).as_slice()) {
+                    (&[i], &[j]) => {
+                        touch(i, &mut conflicts);
+                        touch(j, &mut conflicts);
+                        // the classes move, the times stay put
+                        let (si, sj) = (cs[i].clone(), cs[j].clone());
+                        cs[i] = ClassSlot { start: si.start, end: si.end, ..sj };
+                        cs[j] = ClassSlot { start: sj.start, end: sj.end, ..si };
+                    },
+                    ([], _) => nothing(a, &mut conflicts),
+                    (_, []) => nothing(b, &mut conflicts),
+                    _ => conflicts.push(conflict("SWAP needs exactly one period on each side".to_string())),
+                }
+            },
+            Action::Replace(t, with) => {
+                let hits = t.resolve(cs);
+                if hits.is_empty() {
+                    nothing(t, &mut conflicts);
+                }
+                for i in hits {
+                    touch(i, &mut conflicts);
+                    cs[i].name = with.clone();
+                    cs[i].substituted = true;
+                    cs[i].blanked = false;
+                }
+            },
+            Action::Delete(t) => {
+                let hits = t.resolve(cs);
+                if hits.is_empty() {
+                    nothing(t, &mut conflicts);
+                }
+                for i in hits {
+                    touch(i, &mut conflicts);
+                    cs[i].name = " ".to_string();
+                    cs[i].substituted = false;
+                    cs[i].blanked = true;
+                }
+            },
+            Action::Note(t, text) => {
+                let hits = t.resolve(cs);
+                if hits.is_empty() {
+                    nothing(t, &mut conflicts);
+                }
+                // notes pile up instead of fighting
+                for i in hits {
+                    cs[i].note = Some(match cs[i].note.take() {
+                        Some(n) => format!("{}; {}", n, text),
+                        None => text.clone(),
+                    });
+                }
+            },
+            Action::Insert(p, name) => {
+                if let Some((_, other)) = inserted.iter().find(|(q, _)| q == p) {
+                    conflicts.push(conflict(format!("{} already inserts a period {}", other.place(), p)));
+                }
+                inserted.push((*p, row));
+                let at = (*p - 1).min(cs.len());
+                cs.insert(at, ClassSlot {
+                    name: name.clone(),
+                    timetable: String::new(),
+                    substituted: true,
+                    blanked: false,
+                    note: None,
+                    start: None,
+                    end: None,
+                });
+            },
+        }
+    }
+
+    (ctd, conflicts)
+}
@@ -3,23 +3,57 @@
 #[macro_use] extern crate rocket;
 
 use chrono::{Duration, Timelike, NaiveDate, Local, TimeZone, NaiveDateTime, Datelike};
+use rocket::http::{ContentType, Cookie, Cookies};
+use rocket::response::content::Content;
+use rocket::response::Redirect;
+use rocket::State;
 use rocket_contrib::templates::Template;
-use crate::blocks::{Block, Day};
+use crate::blocks::Block;
+use crate::days::Day;
+use crate::calendar::{CalendarStore, Registry, ISO_FMT};
+use crate::profile::{Profile, SavedProfile, Split, GRADE_COOKIE, PROFILE_COOKIE};
+use crate::schedule::{Cancellation, ScheduleDay};
 use crate::stat::*;
 use serde::{Deserialize, Serialize};
 
+mod api;
+mod bells;
 mod blocks;
+mod calendar;
+mod countdown;
+mod ctd;
+mod days;
+mod generate;
+mod ics;
+mod lookup;
+mod markup;
+mod month;
+mod profile;
+mod recur;
+mod schedule;
+mod specials;
 mod stat;
+mod terms;
+mod validate;
+mod week;
+mod whatif;
 
 #[derive(serde::Serialize)]
 struct TemplateContext<'r> {
     blocks: &'r Vec<blocks::Block>,
     stat: &'r Stat,
+    class_stats: &'r Vec<ClassStat>,
+    terms: &'r Vec<(terms::Term, Stat)>,
+    countdowns: &'r Vec<countdown::Countdown>,
     show_banner: &'r i32,
     nextcount: &'r i32,
     benchmark_duration_ms: &'r f64,
     benchmark_stat_pct: &'r String,
     timetravel: &'r i32,
+    keep_query: &'r String,
+    /// keep_query for the links to the other pages
+    page_query: &'r String,
+    grade_notice: &'r Option<String>,
 }
 
 #[derive(serde::Serialize)]
@@ -33,40 +67,61 @@ enum TimeTravel {
     Failed = 2
 }
 
-#[get("/sched?<count>&<dt>")]
-fn sched(count: Option<i32>, dt: Option<String>) -> Template {
+#[derive(serde::Serialize)]
+struct ProfileTemplateContext<'r> {
+    splits: &'r Vec<Split>,
+    code: &'r String,
+    grade: &'r str,
+    grades: &'r Vec<&'r str>,
+    grade_notice: &'r Option<String>,
+}
+
+#[get("/sched?<count>&<dt>&<p>&<grade>")]
+fn sched(
+    store: State<CalendarStore>,
+    saved: SavedProfile,
+    count: Option<i32>,
+    dt: Option<String>,
+    p: Option<String>,
+    grade: Option<String>,
+) -> Template {
     // first, prevent silly nonsense like requesting a BILLION things
-    match count {
-        Some(c) => {
-            if c > 160 {
-                return Template::render("silly", &SillyTemplateContext {
-                    count: &c
-                })
-            }
+    if let Some(c) = count {
+        if c > 160 {
+            return Template::render("silly", SillyTemplateContext {
+                count: &c
+            })
         }
-        None => {}
     }
 
     let benchmark_dt_start = chrono::Local::now();
+    let reg = store.get();
+    // a profile or grade in the url has to stay in the url when loading more days
+    let keep_query = format!(
+        "{}{}",
+        p.as_ref().map(|p| format!("&p={}", p)).unwrap_or_default(),
+        grade.as_ref().map(|g| format!("&grade={}", g)).unwrap_or_default(),
+    );
+    let page_query = start_query(&p, &grade);
+    let code = saved.code(p);
+    let grade = saved.grade(grade);
 
     // figure it out
     let mut timetravel = TimeTravel::False;
     let now = if dt.is_some() {
         let date_p = NaiveDateTime::parse_from_str(dt.unwrap().as_str(), "%d-%m-%Y-%H-%M-%S");
-        if date_p.unwrap().year() < 2020 { // easy mistake to make
-            timetravel = TimeTravel::Failed;
-            chrono::Local::now()
-        } else {
-            match date_p {
-                Ok(d) => {
-                    timetravel = TimeTravel::True;
-                    Local.from_local_datetime(&d).unwrap()
-                },
-                Err(_) => {
-                    timetravel = TimeTravel::Failed;
-                    chrono::Local::now()
-                } // bad!
-            }
+        // < 2020 is an easy mistake to make, and a time the clocks skip
+        // (daylight saving) isn't a time
+        let local = date_p.ok().filter(|d| d.year() >= 2020).and_then(|d| Local.from_local_datetime(&d).earliest());
+        match local {
+            Some(t) => {
+                timetravel = TimeTravel::True;
+                t
+            },
+            None => {
+                timetravel = TimeTravel::Failed;
+                chrono::Local::now()
+            } // bad!
         }
     } else {
         chrono::Local::now()
@@ -80,7 +135,7 @@ fn sched(count: Option<i32>, dt: Option<String>) -> Template {
     let show_banner = match now.hour() {
         // BANNER TYPES: 0 none | 1 date | 2 zzz
         // this should be an enum but
-        22 | 23 | 24 | 0 => 1,
+        22..=24 | 0 => 1,
         1 => {
             if now.minute() > 29 {
                 2
@@ -88,41 +143,62 @@ fn sched(count: Option<i32>, dt: Option<String>) -> Template {
                 1
             }
         },
-        2 | 3 | 4 => 2,
+        2..=4 => 2,
         _ => 0,
     };
 
 
+    // 160 days from june is next year's calendar, so pick one per block
+    let block = |i: i64, title: &str| {
+        let date = (now + Duration::days(i)).naive_local().date();
+        let cal = reg.select(grade.as_deref(), date);
+        let profile = Profile::decode(cal, &code);
+        Block::from_day(&ScheduleDay::build(cal, date, now.naive_local(), &profile), cal.day_types(), title)
+    };
     let mut bks= vec![
-        blocks::Block::generate(now, now, "Today"),
-        blocks::Block::generate(now + Duration::days(1), now, "Tomorrow"),
-        blocks::Block::generate(now + Duration::days(2), now, "Day after tomorrow"),
-        blocks::Block::generate(now + Duration::days(3), now, "Day after day after tomorrow"),
+        block(0, "Today"),
+        block(1, "Tomorrow"),
+        block(2, "Day after tomorrow"),
+        block(3, "Day after day after tomorrow"),
     ];
 
     // let's try this
-    match count {
-        Some(c) => {
-            for i in 4..=c {
-                // oh hell yeah that's what i'm talking about
-                bks.push(blocks::Block::generate(now + Duration::days(i as i64), now, ""))
-            }
+    if let Some(c) = count {
+        for i in 4..=c {
+            // oh hell yeah that's what i'm talking about
+            bks.push(block(i as i64, ""))
         }
-        None => {}
     }
 
+    let stats_dt_start = chrono::Local::now();
+    let stat_cal = reg.select(grade.as_deref(), now.naive_local().date());
+    let s = generate_stat(stat_cal, now, None);
+    let class_stats = class_stats(stat_cal, now, &Profile::decode(stat_cal, &code), None);
+    // just the term and semester we're in, the api has the rest
+    let today = now.naive_local().date();
+    let terms: Vec<(terms::Term, Stat)> = stat_cal
+        .terms()
+        .iter()
+        .filter(|t| t.contains(&today))
+        .map(|t| (t.clone(), generate_stat(stat_cal, now, Some(t))))
+        .collect();
+    let countdowns = countdown::countdowns(stat_cal, now.naive_local());
+
+    // the stats are the slow part now, so they go in the total
     let benchmark_dt_end = chrono::Local::now();
     let benchmark_duration = benchmark_dt_end - benchmark_dt_start;
     let benchmark_duration_ms = (benchmark_duration.num_microseconds().unwrap() as f64 / 1000.0) + 0.5;
+    let stats_ms = (benchmark_dt_end - stats_dt_start).num_microseconds().unwrap() as f64 / 1000.0;
 
-    let s = generate_stat(now);
-
-    let benchmark_stat_pct = &s.time_ms / benchmark_duration_ms * 100.0;
+    let benchmark_stat_pct = stats_ms / benchmark_duration_ms * 100.0;
 
     // render
-    Template::render("sched", &TemplateContext {
+    Template::render("sched", TemplateContext {
         blocks: &bks,
         stat: &s,
+        class_stats: &class_stats,
+        terms: &terms,
+        countdowns: &countdowns,
         show_banner: &show_banner,
         nextcount: &match count {
             Some(c) => c + 7,
@@ -131,11 +207,15 @@ fn sched(count: Option<i32>, dt: Option<String>) -> Template {
         benchmark_duration_ms: &benchmark_duration_ms,
         benchmark_stat_pct: &format!("{:.3}", benchmark_stat_pct),
         timetravel: &(timetravel as i32),
+        keep_query: &keep_query,
+        page_query: &page_query,
+        grade_notice: &grade_notice(&reg, &grade),
     })
 }
 
-#[get("/api?<date>")]
-fn api(date: String) -> String {
+#[get("/api?<date>&<p>")]
+fn api(store: State<CalendarStore>, saved: SavedProfile, date: String, p: Option<String>) -> String {
+    let reg = store.get();
     let date = if date == "now" {
         // lazy
         chrono::Local::now().naive_local().date()
@@ -147,32 +227,33 @@ fn api(date: String) -> String {
         }
     };
 
-    let day = match Block::day_from_date(&date) {
+    let cal = reg.select(saved.grade(None).as_deref(), date);
+    let profile = Profile::decode(cal, &saved.code(p));
+    let day = match cal.day(&date) {
         Some(d) => d,
         None => return "no_day".to_string(),
     };
     match day {
         Day::Ped |
         Day::Holiday |
+        Day::NoSchool |
         Day::Unknown => return "no_school_day".to_string(),
         _ => {}
     }
 
-    let classes = blocks::Block::classes_from_day(&day).unwrap();
-    let special = blocks::Block::get_special(&date).unwrap_or_default();
-
-    // ok so we know it's a valid day with classes
+    // ok so we know it's a valid day with classes (unless it got cancelled)
+    let sd = ScheduleDay::build(cal, date, chrono::Local::now().naive_local(), &profile);
     let a = ApiBlock {
         date: date.format("%A, %d-%b-%Y").to_string(),
-        day: blocks::Block::format_day(&Some(day)),
-        classes,
-        special,
-        is_online: blocks::Block::check_online(&date)
+        day: sd.label.clone(),
+        classes: sd.class_names(),
+        special: sd.specials.iter().map(|s| s.text.clone()).collect(),
+        is_online: sd.is_online,
+        cancelled: sd.cancelled.map(|c| c.kind),
+        current: sd.current,
     };
 
-    let j = serde_json::to_string(&a).unwrap_or("balls".to_string());
-
-    j
+    serde_json::to_string(&a).unwrap_or_else(|_| "balls".to_string())
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -181,20 +262,318 @@ struct ApiBlock {
     day: String,
     classes: Vec<String>,
     special: Vec<String>,
-    is_online: bool
+    is_online: bool,
+    cancelled: Option<Cancellation>,
+    current: Option<bells::Current>,
 }
 
 #[get("/api")]
 fn api_help() -> String {
-    "api for sched. i'll write docs later.".to_string()
+    "api for sched. docs, sort of:\n\
+    \n\
+    GET /api/v1/day/<yyyy-mm-dd or today>     one day\n\
+    GET /api/v1/range?from=<date>&to=<date>   every day in between (inclusive)\n\
+    GET /api/v1/next-school-day[?from=<date>] the next day with classes\n\
+    GET /api/v1/stats[?date=<date>]           days left, and meetings left/held/lost per class, for the year and each term\n\
+    GET /api/v1/countdowns                    days (and school days) until the next holiday, ped day, exam...\n\
+    GET /api/v1/whatif?edits=<edits>[&weeks=3] the schedule if we cancelled/moved things (nothing's saved)\n\
+        edits: cancel:<date>, snow:<date>, ped:<date>, swap:<date>:<date>, separated by commas\n\
+    \n\
+    they all take ?grade= (which calendar) and ?p= (your split classes).\n\
+    everything's json. errors look like {\"error\": \"bad_date\", \"message\": \"...\"} with a 400 or 404.\n\
+    \n\
+    (the old /api?date=dd-mm-yyyy still works but please don't)".to_string()
 }
 
 
+#[get("/sched.ics?<p>&<grade>")]
+fn sched_ics(store: State<CalendarStore>, saved: SavedProfile, p: Option<String>, grade: Option<String>) -> Content<String> {
+    let reg = store.get();
+    Content(
+        ContentType::new("text", "calendar"),
+        ics::generate(&reg.for_grade(saved.grade(grade).as_deref()), chrono::Local::now().naive_local(), &saved.code(p)),
+    )
+}
+
+#[derive(serde::Serialize)]
+struct WeekTemplateContext<'r> {
+    week: &'r week::Week,
+    keep_query: &'r String,
+    grade_notice: &'r Option<String>,
+}
+
+// the grid, for planning the week. <date> is yyyy-mm-dd or today
+#[get("/week/<date>?<p>&<grade>")]
+fn week_page(store: State<CalendarStore>, saved: SavedProfile, date: String, p: Option<String>, grade: Option<String>) -> Option<Template> {
+    let reg = store.get();
+    let now = chrono::Local::now().naive_local();
+    let date = match date.as_str() {
+        "today" => now.date(),
+        d => NaiveDate::parse_from_str(d, ISO_FMT).ok()?,
+    };
+    let keep_query = start_query(&p, &grade);
+
+    let code = saved.code(p);
+    let grade = saved.grade(grade);
+    Some(Template::render("week", WeekTemplateContext {
+        week: &week::build(&reg, grade.as_deref(), &code, date, now),
+        keep_query: &keep_query,
+        grade_notice: &grade_notice(&reg, &grade),
+    }))
+}
+
+#[get("/week?<p>&<grade>")]
+fn week_today(store: State<CalendarStore>, saved: SavedProfile, p: Option<String>, grade: Option<String>) -> Option<Template> {
+    week_page(store, saved, "today".to_string(), p, grade)
+}
+
+// a grade with no calendar still gets grade 11's pages, but they say so
+fn grade_notice(reg: &Registry, grade: &Option<String>) -> Option<String> {
+    grade
+        .as_ref()
+        .filter(|g| !reg.knows(g))
+        .map(|g| format!("There's no grade {} calendar, so this is grade {}'s.", g, calendar::BUILTIN_GRADE))
+}
+
+// same as /sched's keep_query, but it starts the query string
+fn start_query(p: &Option<String>, grade: &Option<String>) -> String {
+    [
+        p.as_ref().map(|p| format!("p={}", p)),
+        grade.as_ref().map(|g| format!("grade={}", g)),
+    ].iter().flatten().fold(String::new(), |q, x| format!("{}{}{}", q, if q.is_empty() {"?"} else {"&"}, x))
+}
+
+#[derive(serde::Serialize)]
+struct MonthTemplateContext<'r> {
+    title: &'r str,
+    months: &'r Vec<month::Month>,
+    legend: &'r Vec<month::LegendEntry>,
+    /// (what it says, where it goes)
+    nav: &'r Vec<(String, String)>,
+    keep_query: &'r String,
+    grade_notice: &'r Option<String>,
+}
+
+// every date colored by its day type. <month> is yyyy-mm or today
+#[get("/month/<month>?<p>&<grade>")]
+fn month_page(store: State<CalendarStore>, saved: SavedProfile, month: String, p: Option<String>, grade: Option<String>) -> Option<Template> {
+    let reg = store.get();
+    let now = chrono::Local::now().naive_local();
+    let first = match month.as_str() {
+        "today" => now.date().with_day(1)?,
+        m => NaiveDate::parse_from_str(&format!("{}-01", m), ISO_FMT).ok()?,
+    };
+    let keep_query = start_query(&p, &grade);
+    let grade = saved.grade(grade);
+
+    let prev = (first - Duration::days(1)).with_day(1)?;
+    let next = (first + Duration::days(32)).with_day(1)?;
+    let link = |d: NaiveDate| format!("/month/{}{}", d.format(month::MONTH_FMT), keep_query);
+    Some(Template::render("month", MonthTemplateContext {
+        title: &first.format("%B %Y").to_string(),
+        months: &vec![month::build(&reg, grade.as_deref(), first, now)],
+        legend: &month::legend(reg.select(grade.as_deref(), first)),
+        nav: &vec![
+            (format!("← {}", prev.format("%B")), link(prev)),
+            ("the whole year".to_string(), format!("/year{}", keep_query)),
+            (format!("{} →", next.format("%B")), link(next)),
+        ],
+        keep_query: &keep_query,
+        grade_notice: &grade_notice(&reg, &grade),
+    }))
+}
+
+// a whole school year of months. ?year=2021-2022, this one if there's none
+#[get("/year?<year>&<p>&<grade>")]
+fn year_page(store: State<CalendarStore>, saved: SavedProfile, year: Option<String>, p: Option<String>, grade: Option<String>) -> Option<Template> {
+    let reg = store.get();
+    let now = chrono::Local::now().naive_local();
+    let keep_query = start_query(&p, &grade);
+    let grade = saved.grade(grade);
+    let cals = reg.for_grade(grade.as_deref());
+    let cal = match year {
+        Some(y) => *cals.iter().find(|c| c.year() == y)?,
+        None => reg.select(grade.as_deref(), now.date()),
+    };
+
+    // this grade's other years
+    let nav = cals
+        .iter()
+        .filter(|c| c.year() != cal.year())
+        .map(|c| (c.year().to_string(), format!("/year?year={}{}", c.year(), keep_query.replacen('?', "&", 1))))
+        .collect();
+    Some(Template::render("month", MonthTemplateContext {
+        title: &format!("{} (grade {})", cal.year(), cal.grade()),
+        months: &month::year(&reg, cal, now),
+        legend: &month::legend(cal),
+        nav: &nav,
+        keep_query: &keep_query,
+        grade_notice: &grade_notice(&reg, &grade),
+    }))
+}
+
+// pick which side of each split class you're in
+#[get("/profile?<p>&<grade>")]
+fn profile_page(store: State<CalendarStore>, saved: SavedProfile, p: Option<String>, grade: Option<String>) -> Template {
+    let reg = store.get();
+    let grade = saved.grade(grade);
+    let cal = reg.select(grade.as_deref(), chrono::Local::now().naive_local().date());
+    let profile = Profile::decode(cal, &saved.code(p));
+    Template::render("profile", ProfileTemplateContext {
+        splits: &profile.choices(cal),
+        code: &profile.encode(cal),
+        grade: cal.grade(),
+        grades: &reg.grades(),
+        grade_notice: &grade_notice(&reg, &grade),
+    })
+}
+
+// an empty value forgets it, a missing one leaves it alone
+#[get("/profile/save?<p>&<grade>")]
+fn profile_save(mut cookies: Cookies, p: Option<String>, grade: Option<String>) -> Redirect {
+    for (name, value) in [(PROFILE_COOKIE, p), (GRADE_COOKIE, grade)] {
+        match value {
+            Some(v) if !v.is_empty() => cookies.add(
+                Cookie::build(name, v)
+                    .path("/")
+                    .permanent()
+                    .finish()
+            ),
+            Some(_) => cookies.remove(Cookie::named(name)),
+            None => {},
+        }
+    }
+    Redirect::to("/sched")
+}
+
+/// Loads the calendars for the command line stuff, or gives up.
+fn load_or_exit() -> Registry {
+    match Registry::load() {
+        Ok(reg) => reg,
+        Err(e) => {
+            eprintln!("couldn't load the calendar: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|a| a.as_str()) {
+        // `sched validate` just checks the data and leaves
+        Some("validate") => {
+            let issues = validate::validate();
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            println!("{} problem(s) found", issues.len());
+            std::process::exit(if issues.is_empty() {0} else {1});
+        },
+        // `sched export-ics [file] [profile code] [grade]`, stdout if there's no file
+        Some("export-ics") => {
+            let reg = load_or_exit();
+            let cals = reg.for_grade(args.get(4).map(|g| g.as_str()));
+            let code = args.get(3).map(|p| p.as_str()).unwrap_or_default();
+            let out = ics::generate(&cals, chrono::Local::now().naive_local(), code);
+            match args.get(2) {
+                Some(path) => if let Err(e) = std::fs::write(path, out) {
+                    eprintln!("couldn't write {}: {}", path, e);
+                    std::process::exit(1);
+                },
+                None => print!("{}", out),
+            }
+            return;
+        },
+        // `sched whatif <edits> [weeks] [grade]`, same edits as the api
+        Some("whatif") => {
+            let reg = load_or_exit();
+            let result = whatif::Edit::parse_list(args.get(2).map(|e| e.as_str()).unwrap_or_default())
+                .and_then(|edits| {
+                    let first = edits.iter().flat_map(|e| e.dates()).min().unwrap_or_else(|| Local::now().naive_local().date());
+                    let cal = reg.select(args.get(4).map(|g| g.as_str()), first);
+                    let weeks = match args.get(3) {
+                        Some(w) => w.parse().map_err(|_| format!("'{}' isn't a number of weeks", w))?,
+                        None => whatif::DEFAULT_WEEKS,
+                    };
+                    whatif::simulate(cal, edits, Local::now(), "", weeks)
+                });
+            match result {
+                Ok(w) => {
+                    for d in &w.days {
+                        let after = match &d.after.cancelled {
+                            Some(c) => format!("{} ({:?})", d.after.label, c.kind),
+                            None => d.after.label.clone(),
+                        };
+                        println!("{} {} {} -> {}", if d.changed {"*"} else {" "}, d.date.format("%a %Y-%m-%d"), d.before, after);
+                    }
+                    println!();
+                    println!("days remaining: {} -> {}", w.stat_before.days_rem, w.stat_after.days_rem);
+                    println!(
+                        "ped days / holidays: {} left, {} done -> {} left, {} done",
+                        w.stat_before.ped_rem, w.stat_before.ped_past, w.stat_after.ped_rem, w.stat_after.ped_past,
+                    );
+                },
+                Err(e) => {
+                    eprintln!("usage: sched whatif cancel:<date>,snow:<date>,ped:<date>,swap:<date>:<date> [weeks] [grade]");
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                },
+            }
+            return;
+        },
+        // `sched generate <rules file> [sched data file]`: prints the calendar,
+        // or with a file, what's different about it
+        Some("generate") => {
+            let reg = load_or_exit();
+            let day_types = reg.calendars()[0].day_types();
+            let path = match args.get(2) {
+                Some(p) => p,
+                None => {
+                    eprintln!("usage: sched generate <rules file> [sched data file to diff against]");
+                    std::process::exit(2);
+                },
+            };
+            let days = match generate::Rules::load(path, day_types).map_err(|e| e.to_string()).and_then(|r| r.generate(day_types)) {
+                Ok(days) => days,
+                Err(e) => {
+                    eprintln!("couldn't generate: {}", e);
+                    std::process::exit(1);
+                },
+            };
+            match args.get(3) {
+                Some(existing) => {
+                    let text = std::fs::read_to_string(existing).unwrap_or_else(|e| {
+                        eprintln!("couldn't read {}: {}", existing, e);
+                        std::process::exit(1);
+                    });
+                    let diffs = generate::diff(&days, existing, &text);
+                    for d in &diffs {
+                        println!("{}", d);
+                    }
+                    println!("{} difference(s)", diffs.len());
+                    std::process::exit(if diffs.is_empty() {0} else {1});
+                },
+                None => for (date, code) in days {
+                    println!("{},{}", date.format(calendar::DATE_FMT), code);
+                },
+            }
+            return;
+        },
+        _ => {},
+    }
+
+    // otherwise still check, but only complain
+    for issue in validate::validate() {
+        eprintln!("warning: {}", issue);
+    }
+
+    // nothing good to fall back on yet, so if this fails don't even start
+    let store = CalendarStore::new(load_or_exit());
+    store.watch();
+
     rocket::ignite()
-        .mount("/", routes![sched, api, api_help])
-        .attach(Template::custom(|engines| {
-            engines.tera.autoescape_on(vec![]) // probably secure :)
-        }))
+        .manage(store)
+        .mount("/", routes![sched, sched_ics, week_page, week_today, month_page, year_page, profile_page, profile_save, api, api_help, api::day, api::range, api::next_school_day, api::stats, api::countdowns, api::whatif])
+        .attach(Template::fairing())
         .launch();
 }
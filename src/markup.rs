@@ -0,0 +1,211 @@
+// Markup
+// the little bit of formatting specials get: **bold**, *italic* and
+// [links](https://...). everything else is just text, so whatever ends up in
+// special.csv can't put its own html on the page. \* is a plain *
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Bold(Vec<Node>),
+    Italic(Vec<Node>),
+    Link(String, String),
+}
+
+/// Links have to go somewhere normal: no javascript: and friends.
+fn safe_url(url: &str) -> bool {
+    let lower = url.to_ascii_lowercase();
+    ["http://", "https://", "mailto:"].iter().any(|p| lower.starts_with(p))
+        || (url.starts_with('/') && !url.starts_with("//"))
+}
+
+fn parse(s: &str) -> Vec<Node> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        let mut found = None;
+        if let Some(after) = rest.strip_prefix("**") {
+            if let Some(end) = after.find("**").filter(|&e| e > 0) {
+                found = Some((Node::Bold(parse(&after[..end])), 2 + end + 2));
+            }
+        } else if let Some(after) = rest.strip_prefix('*') {
+            if let Some(end) = after.find('*').filter(|&e| e > 0) {
+                found = Some((Node::Italic(parse(&after[..end])), 1 + end + 1));
+            }
+        } else if let Some(after) = rest.strip_prefix('[') {
+            if let Some(mid) = after.find("](") {
+                if let Some(end) = after[mid + 2..].find(')') {
+                    let url = &after[mid + 2..mid + 2 + end];
+                    if safe_url(url) {
+                        found = Some((Node::Link(after[..mid].to_string(), url.to_string()), 1 + mid + 2 + end + 1));
+                    }
+                }
+            }
+        } else if c == '\\' {
+            if let Some(next) = rest[1..].chars().next() {
+                text.push(next);
+                rest = &rest[1 + next.len_utf8()..];
+                continue;
+            }
+        }
+
+        match found {
+            Some((node, len)) => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                nodes.push(node);
+                rest = &rest[len..];
+            },
+            None => {
+                text.push(c);
+                rest = &rest[c.len_utf8()..];
+            },
+        }
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    nodes
+}
+
+/// Plain text made safe for html.
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#x27;")
+}
+
+fn html(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(&escape(t)),
+            Node::Bold(inner) => {
+                out.push_str("<b>");
+                html(inner, out);
+                out.push_str("</b>");
+            },
+            Node::Italic(inner) => {
+                out.push_str("<i>");
+                html(inner, out);
+                out.push_str("</i>");
+            },
+            Node::Link(text, url) => {
+                out.push_str(&format!("<a href=\"{}\" rel=\"noopener nofollow\">{}</a>", escape(url), escape(text)));
+            },
+        }
+    }
+}
+
+fn plain(nodes: &[Node], out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(t) => out.push_str(t),
+            Node::Bold(inner) | Node::Italic(inner) => plain(inner, out),
+            Node::Link(text, url) => out.push_str(&format!("{} ({})", text, url)),
+        }
+    }
+}
+
+/// Safe to put on the page as is (everything that isn't markup is escaped).
+pub fn to_html(s: &str) -> String {
+    let mut out = String::new();
+    html(&parse(s), &mut out);
+    out
+}
+
+/// For places that don't do html (the calendar feed).
+pub fn to_plain(s: &str) -> String {
+    let mut out = String::new();
+    plain(&parse(s), &mut out);
+    out
+}
+
+/// Links that won't be links because of where they go, for validate.
+pub fn bad_links(s: &str) -> Vec<String> {
+    let mut bad = vec![];
+    let mut rest = s;
+    while let Some(mid) = rest.find("](") {
+        let after = &rest[mid + 2..];
+        match after.find(')') {
+            Some(end) => {
+                if !safe_url(&after[..end]) {
+                    bad.push(after[..end].to_string());
+                }
+                rest = &after[end..];
+            },
+            None => break,
+        }
+    }
+    bad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_is_just_text() {
+        assert_eq!(escape(r#"<a href="x">'&'</a>"#), "&lt;a href=&quot;x&quot;&gt;&#x27;&amp;&#x27;&lt;/a&gt;");
+        assert_eq!(to_html("<script>alert(1)</script>"), "&lt;script&gt;alert(1)&lt;/script&gt;");
+        assert_eq!(to_html("**<script>**"), "<b>&lt;script&gt;</b>");
+        assert_eq!(to_html("[<img src=x>](/a)"), "<a href=\"/a\" rel=\"noopener nofollow\">&lt;img src=x&gt;</a>");
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(to_html("**Bring** a *pencil*"), "<b>Bring</b> a <i>pencil</i>");
+        assert_eq!(to_html("**a *b* c**"), "<b>a <i>b</i> c</b>");
+        assert_eq!(to_html(r"2 \* 3"), "2 * 3");
+    }
+
+    #[test]
+    fn unbalanced_stars_stay_stars() {
+        assert_eq!(to_html("2 * 3"), "2 * 3");
+        assert_eq!(to_html("**not bold"), "**not bold");
+        assert_eq!(to_html("*not italic"), "*not italic");
+        assert_eq!(to_html("****"), "****");
+        assert_eq!(to_html("**a*"), "*<i>a</i>");
+    }
+
+    #[test]
+    fn only_normal_links() {
+        assert!(safe_url("https://example.com"));
+        assert!(safe_url("HTTP://example.com"));
+        assert!(safe_url("mailto:office@example.com"));
+        assert!(safe_url("/week/today"));
+        assert!(!safe_url("javascript:alert(1)"));
+        assert!(!safe_url("JavaScript:alert(1)"));
+        assert!(!safe_url(" javascript:alert(1)"));
+        assert!(!safe_url("data:text/html,hi"));
+        assert!(!safe_url("//evil.host/x"));
+        assert!(!safe_url("evil.host"));
+
+        assert_eq!(to_html("[click](javascript:alert(1))"), "[click](javascript:alert(1))");
+        assert_eq!(to_html("[click](//evil.host)"), "[click](//evil.host)");
+        assert_eq!(bad_links("[a](/ok) [b](javascript:x) [c](//evil.host)"), vec!["javascript:x", "//evil.host"]);
+    }
+
+    #[test]
+    fn urls_cant_get_out_of_the_attribute() {
+        assert_eq!(
+            to_html(r#"[x](https://a.com/"onmouseover="alert(1))"#),
+            "<a href=\"https://a.com/&quot;onmouseover=&quot;alert(1\" rel=\"noopener nofollow\">x</a>)",
+        );
+        assert_eq!(
+            to_html("[x](https://a.com/><script>)"),
+            "<a href=\"https://a.com/&gt;&lt;script&gt;\" rel=\"noopener nofollow\">x</a>",
+        );
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(to_plain("**Bring** a *pencil*"), "Bring a pencil");
+        assert_eq!(to_plain("see [the site](https://example.com)"), "see the site (https://example.com)");
+        assert_eq!(to_plain("[x](javascript:alert(1))"), "[x](javascript:alert(1))");
+        // no escaping, the feed isn't html
+        assert_eq!(to_plain("<b> & *it*"), "<b> & it");
+    }
+}
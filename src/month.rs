@@ -0,0 +1,125 @@
+// Month
+// every date colored like its block, a month or a whole school year at a
+// time. for seeing where the breaks and exam blocks fall
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use crate::blocks::Block;
+use crate::calendar::{Calendar, Registry, ISO_FMT};
+use crate::markup;
+use crate::profile::Profile;
+use crate::schedule::ScheduleDay;
+
+pub const MONTH_FMT: &str = "%Y-%m";
+
+#[derive(Serialize, Debug)]
+pub struct MonthCell {
+    day: u32,
+    /// iso, for linking to its week
+    date: String,
+    color: String,
+    /// the day code: the cycle number, P, E...
+    code: String,
+    label: String,
+    /// plain text, they only go in a tooltip
+    specials: Vec<String>,
+    cancelled: bool,
+    today: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Month {
+    title: String,
+    link: String,
+    /// monday to sunday, None outside the month
+    weeks: Vec<Vec<Option<MonthCell>>>,
+}
+
+/// A key for the colors.
+#[derive(Serialize, Debug)]
+pub struct LegendEntry {
+    code: String,
+    name: String,
+    color: String,
+}
+
+/// The month `first` is in.
+pub fn build(reg: &Registry, grade: Option<&str>, first: NaiveDate, now: NaiveDateTime) -> Month {
+    let first = first.with_day(1).unwrap_or(first);
+    let mut weeks = vec![];
+    let mut week: Vec<Option<MonthCell>> = (0..first.weekday().num_days_from_monday()).map(|_| None).collect();
+
+    let mut date = first;
+    while date.month() == first.month() {
+        week.push(Some(cell(reg.select(grade, date), date, now)));
+        if week.len() == 7 {
+            weeks.push(std::mem::take(&mut week));
+        }
+        date += Duration::days(1);
+    }
+    if !week.is_empty() {
+        week.resize_with(7, || None);
+        weeks.push(week);
+    }
+
+    Month {
+        title: first.format("%B %Y").to_string(),
+        link: first.format(MONTH_FMT).to_string(),
+        weeks,
+    }
+}
+
+/// Every month a calendar has days in.
+pub fn year(reg: &Registry, cal: &Calendar, now: NaiveDateTime) -> Vec<Month> {
+    let (mut month, last) = match (cal.first(), cal.last()) {
+        (Some(f), Some(l)) => (f.with_day(1).unwrap_or(f), l),
+        _ => return vec![],
+    };
+    let mut months = vec![];
+    while month <= last {
+        months.push(build(reg, Some(cal.grade()), month, now));
+        // the 1st plus 32 days is always next month
+        month = (month + Duration::days(32)).with_day(1).unwrap_or(month);
+    }
+    months
+}
+
+pub fn legend(cal: &Calendar) -> Vec<LegendEntry> {
+    cal.day_types()
+        .all()
+        .iter()
+        .map(|t| LegendEntry {
+            code: t.code.clone(),
+            name: t.name.clone(),
+            color: t.color.clone(),
+        })
+        .collect()
+}
+
+fn cell(cal: &Calendar, date: NaiveDate, now: NaiveDateTime) -> MonthCell {
+    let today = date == now.date();
+    if cal.day(&date).is_none() {
+        // outside the school year
+        return MonthCell {
+            day: date.day(),
+            date: date.format(ISO_FMT).to_string(),
+            color: "#1A1A1A".to_string(),
+            code: String::new(),
+            label: "no school year".to_string(),
+            specials: vec![],
+            cancelled: false,
+            today,
+        };
+    }
+    let sd = ScheduleDay::build(cal, date, now, &Profile::default());
+    MonthCell {
+        day: date.day(),
+        date: date.format(ISO_FMT).to_string(),
+        color: Block::bgcolor(&sd, cal.day_types()),
+        code: sd.day.as_ref().and_then(|d| cal.day_types().code(d)).unwrap_or_default().to_string(),
+        label: sd.label.clone(),
+        specials: sd.specials.iter().map(|s| markup::to_plain(&s.text)).collect(),
+        cancelled: sd.cancelled.is_some(),
+        today,
+    }
+}
@@ -0,0 +1,131 @@
+// Profiles
+// "Chemistry / Art" means chemistry for some people and art for the rest.
+// a profile is which side of each split class you're on, packed into a
+// short code (one character per split) that lives in a cookie or the url
+
+use std::collections::BTreeMap;
+
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use serde::Serialize;
+use crate::calendar::Calendar;
+
+pub const PROFILE_COOKIE: &str = "profile";
+pub const GRADE_COOKIE:   &str = "grade";
+
+// what a split class looks like in sched_classes.csv
+const SPLIT_SEP: &str = " / ";
+// in the code: no choice made for this split
+const NO_CHOICE: char = '-';
+
+/// One split class and the options in it.
+#[derive(Serialize, Debug, Clone)]
+pub struct Split {
+    pub cell: String,
+    pub options: Vec<String>,
+    /// which option this profile picked, if any
+    pub chosen: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    choices: BTreeMap<String, String>,
+}
+
+impl Profile {
+    /// Every split class in the timetables, in code order: the normal
+    /// timetable's (sorted), then whatever new ones each term's adds. A term
+    /// only ever adds on to the end, so codes people already have still work.
+    pub fn splits(cal: &Calendar) -> Vec<Split> {
+        let mut cells: Vec<&String> = vec![];
+        for timetable in cal.timetables() {
+            let mut new: Vec<&String> = timetable
+                .iter()
+                .flatten()
+                .filter(|c| c.contains(SPLIT_SEP) && !cells.contains(c))
+                .collect();
+            new.sort();
+            new.dedup();
+            cells.extend(new);
+        }
+        cells
+            .into_iter()
+            .map(|c| Split {
+                cell: c.clone(),
+                options: c.split(SPLIT_SEP).map(|o| o.trim().to_string()).collect(),
+                chosen: None,
+            })
+            .collect()
+    }
+
+    /// Anything weird in the code (wrong length, bad digits) just means "no choice".
+    pub fn decode(cal: &Calendar, code: &str) -> Self {
+        let mut choices = BTreeMap::new();
+        for (split, c) in Self::splits(cal).into_iter().zip(code.chars()) {
+            let option = c.to_digit(10).and_then(|i| split.options.get(i as usize));
+            if let Some(o) = option {
+                choices.insert(split.cell.clone(), o.clone());
+            }
+        }
+        Profile { choices }
+    }
+
+    /// The splits with this profile's choices filled in.
+    pub fn choices(&self, cal: &Calendar) -> Vec<Split> {
+        Self::splits(cal)
+            .into_iter()
+            .map(|mut s| {
+                s.chosen = self.choices.get(&s.cell).and_then(|o| s.options.iter().position(|x| x == o));
+                s
+            })
+            .collect()
+    }
+
+    pub fn encode(&self, cal: &Calendar) -> String {
+        self.choices(cal)
+            .iter()
+            .map(|s| match s.chosen {
+                Some(i) => std::char::from_digit(i as u32, 10).unwrap_or(NO_CHOICE),
+                None => NO_CHOICE,
+            })
+            .collect()
+    }
+
+    /// What a timetable cell actually is for this person.
+    pub fn resolve(&self, cell: &str) -> String {
+        match self.choices.get(cell) {
+            Some(o) => o.clone(),
+            None => cell.to_string(),
+        }
+    }
+}
+
+/// The profile code and grade saved in cookies, if there are any.
+pub struct SavedProfile {
+    code: Option<String>,
+    grade: Option<String>,
+}
+
+impl SavedProfile {
+    /// A `?p=` in the url wins over the cookie.
+    pub fn code(&self, p: Option<String>) -> String {
+        p.or_else(|| self.code.clone()).unwrap_or_default()
+    }
+
+    /// Same for `?grade=`.
+    pub fn grade(&self, grade: Option<String>) -> Option<String> {
+        grade.or_else(|| self.grade.clone())
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for SavedProfile {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let cookies = request.cookies();
+        Outcome::Success(SavedProfile {
+            code: cookies.get(PROFILE_COOKIE).map(|c| c.value().to_string()),
+            grade: cookies.get(GRADE_COOKIE).map(|c| c.value().to_string()),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+// Recurring
+// the chess club that meets every monday and the day 3 rehearsal, written
+// down once in recurring.csv instead of once per date. rows are
+//   SPECIAL,when,from,to,except,text,start,end,category,location,audience
+//   LOOKUP,when,from,to,except,what,value
+// `when` is a weekday (Mon) or a cycle day (day 3), optionally /N for every
+// Nth week (counting from the week `from` is in, or the calendar's first week
+// if it's blank). from and to can be blank for the whole year, and except is dates separated by semicolons. they only
+// land on days with classes: no chess club on a ped day
+
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use crate::calendar::DATE_FMT;
+use crate::days::{Day, DayTypes};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum When {
+    Weekday(Weekday),
+    Cycle(usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Recurrence {
+    when: When,
+    every: i64,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    except: BTreeSet<NaiveDate>,
+}
+
+impl Recurrence {
+    /// The `when,from,to,except` columns, starting at `record[1]`. Errors
+    /// have the column.
+    pub fn parse(record: &csv::StringRecord, day_types: &DayTypes) -> Result<Self, (usize, String)> {
+        let opt = |i: usize| record.get(i).map(|s| s.trim()).filter(|s| !s.is_empty());
+        let date = |i: usize, s: &str| NaiveDate::parse_from_str(s.trim(), DATE_FMT).map_err(|_| (i + 1, format!("bad date '{}'", s)));
+
+        let raw = opt(1).unwrap_or_default();
+        let (what, every) = match raw.split_once('/') {
+            Some((w, n)) => match n.trim().parse::<i64>() {
+                Ok(n) if n >= 1 => (w.trim(), n),
+                _ => return Err((2, format!("'{}' after the / isn't a number of weeks", n))),
+            },
+            None => (raw, 1),
+        };
+        let cycle_length = day_types.cycle_length();
+        let when = if let Some(n) = what.strip_prefix("day") {
+            match n.trim().parse::<usize>() {
+                Ok(n) if (1..=cycle_length).contains(&n) => When::Cycle(n),
+                _ => return Err((2, format!("'{}' isn't a day of the {} day cycle", what, cycle_length))),
+            }
+        } else {
+            match what.parse::<Weekday>() {
+                Ok(w) => When::Weekday(w),
+                Err(_) => return Err((2, format!("'{}' isn't a weekday (Mon) or a cycle day (day 3)", raw))),
+            }
+        };
+
+        let from = opt(2).map(|s| date(2, s)).transpose()?;
+        let to = opt(3).map(|s| date(3, s)).transpose()?;
+        if let (Some(f), Some(t)) = (from, to) {
+            if t < f {
+                return Err((4, "ends before it starts".to_string()));
+            }
+        }
+        let except = opt(4)
+            .unwrap_or_default()
+            .split(';')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| date(4, s))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Recurrence {
+            when,
+            every,
+            from,
+            to,
+            except,
+        })
+    }
+
+    /// Which day of the cycle, if it's a cycle day one.
+    pub fn cycle_day(&self) -> Option<usize> {
+        match self.when {
+            When::Cycle(n) => Some(n),
+            When::Weekday(_) => None,
+        }
+    }
+
+    /// Whether it happens on `date`, which is `day`. `first` is the
+    /// calendar's first day, for /N without a from.
+    pub fn on(&self, date: &NaiveDate, day: &Day, first: NaiveDate) -> bool {
+        if !matches!(day, Day::Cycle(_)) || self.except.contains(date) {
+            return false;
+        }
+        if self.from.map(|f| *date < f).unwrap_or(false) || self.to.map(|t| *date > t).unwrap_or(false) {
+            return false;
+        }
+        let hit = match self.when {
+            When::Weekday(w) => date.weekday() == w,
+            When::Cycle(n) => *day == Day::Cycle(n),
+        };
+        hit && (self.every == 1 || self.week(date, first) % self.every == 0)
+    }
+
+    // weeks since the one `from` (or the calendar) starts in, so /2 starts on
+    // the first week. not iso weeks, those start over in january
+    fn week(&self, date: &NaiveDate, first: NaiveDate) -> i64 {
+        let monday = |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+        (monday(*date) - monday(self.from.unwrap_or(first))).num_days() / 7
+    }
+}
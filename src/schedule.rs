@@ -0,0 +1,208 @@
+// Schedule
+// one day of school, worked out once: day type, the classes that are actually
+// happening (after lookup.csv is done with them), specials, cancellations.
+// the page (Block) and the api both just render one of these
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use crate::bells::Current;
+use crate::days::Day;
+use crate::calendar::Calendar;
+use crate::lookup;
+use crate::profile::Profile;
+use crate::specials::{Cancelled, Special};
+use crate::terms::TermKind;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum DayStatus {
+    NotStarted = 0,
+    Normal = 1,
+    IsOver = 2,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Cancellation {
+    Cancelled,
+    SnowDay,
+}
+
+impl Cancellation {
+    // what special.csv used to be limited to: a magic special
+    pub fn from_sentinel(s: &str) -> Option<Self> {
+        [Cancellation::Cancelled, Cancellation::SnowDay]
+            .iter()
+            .copied()
+            .find(|c| s.trim() == c.sentinel())
+    }
+
+    pub fn sentinel(&self) -> &'static str {
+        match self {
+            Cancellation::Cancelled => "*CANC",
+            Cancellation::SnowDay => "*CANCSNOW",
+        }
+    }
+}
+
+/// What a cancelled cycle day does to the rest of the year (cancel_modes.csv).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelMode {
+    /// that day's classes just don't happen, everything after stays put
+    Skip,
+    /// the next school day is that day instead, and so on to the end of the year
+    Shift,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ClassSlot {
+    /// what's actually happening
+    pub name: String,
+    /// what the timetable says normally happens
+    pub timetable: String,
+    /// swapped for something else by lookup.csv
+    pub substituted: bool,
+    /// emptied out by a CTD mode or a DELETE
+    pub blanked: bool,
+    /// a NOTE from lookup.csv ("room 204")
+    pub note: Option<String>,
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+}
+
+/// A "change the day" from lookup.csv.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Ctd {
+    pub mode: String,
+    pub label: String,
+    pub color: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct ScheduleDay {
+    pub date: NaiveDate,
+    pub day: Option<Day>,
+    /// "Day 3", "a Ped Day", or whatever the CTD says
+    pub label: String,
+    pub ctd: Option<Ctd>,
+    /// None means there are no classes that day at all
+    pub classes: Option<Vec<ClassSlot>>,
+    pub specials: Vec<Special>,
+    pub cancelled: Option<Cancelled>,
+    /// names, from terms.csv
+    pub term: Option<String>,
+    pub semester: Option<String>,
+    pub status: DayStatus,
+    pub current: Option<Current>,
+    pub is_online: bool,
+}
+
+impl ScheduleDay {
+    pub fn build(cal: &Calendar, date: NaiveDate, now: NaiveDateTime, profile: &Profile) -> Self {
+        let day = cal.day(&date);
+        let specials = cal.specials(&date);
+        let term = cal.term(&date, TermKind::Term).map(|t| t.name.clone());
+        let semester = cal.term(&date, TermKind::Semester).map(|t| t.name.clone());
+
+        let cancelled = cal.cancelled(&date).cloned();
+        if cancelled.is_some() {
+            return ScheduleDay {
+                date,
+                label: cal.day_types().name(&day),
+                day,
+                ctd: None,
+                classes: None,
+                specials,
+                cancelled,
+                term,
+                semester,
+                status: DayStatus::Normal,
+                current: None,
+                is_online: cal.is_online(&date),
+            };
+        }
+
+        let bells = day.as_ref().and_then(|d| cal.bells(&date, d));
+        let mut classes = timetable_classes(cal, &date, &day, profile);
+        let (ctd, _) = lookup::apply(&cal.lookup(&date), &mut classes, cal.ctd_modes());
+        let ctd = ctd.map(|mode| Ctd {
+            mode: mode.name.clone(),
+            label: mode.label.clone(),
+            color: mode.color.clone(),
+        });
+
+        // times go on after, an INSERT moves everything down
+        let periods = bells.map(|b| b.periods()).unwrap_or_default();
+        for (i, slot) in classes.iter_mut().flatten().enumerate() {
+            slot.start = periods.get(i).map(|p| p.start);
+            slot.end = periods.get(i).map(|p| p.end);
+        }
+
+        // status comes from the bell schedule
+        let status = match bells {
+            Some(b) if date == now.date() => {
+                let t = now.time();
+                if b.start().map(|s| t < s).unwrap_or(false) {
+                    DayStatus::NotStarted
+                } else if b.end().map(|e| t >= e).unwrap_or(false) {
+                    DayStatus::IsOver
+                } else {
+                    DayStatus::Normal
+                }
+            },
+            _ => DayStatus::Normal // not over, it never started (or no bells anyway)
+        };
+
+        // what's happening right now (only for today, obviously)
+        let names: Vec<String> = classes.iter().flatten().map(|c| c.name.clone()).collect();
+        let current = match bells {
+            Some(b) if date == now.date() => b.current(now.time()).map(|c| c.with_classes(&names)),
+            _ => None,
+        };
+
+        ScheduleDay {
+            date,
+            label: match &ctd {
+                Some(c) => c.label.clone(),
+                None => cal.day_types().name(&day),
+            },
+            day,
+            ctd,
+            classes,
+            specials,
+            cancelled: None,
+            term,
+            semester,
+            status,
+            current,
+            is_online: cal.is_online(&date),
+        }
+    }
+
+    /// Just the names of the classes, what the old api used to give out.
+    pub fn class_names(&self) -> Vec<String> {
+        self.classes.iter().flatten().map(|c| c.name.clone()).collect()
+    }
+}
+
+/// What the timetable says for a day, before lookup.csv gets to it.
+pub fn timetable_classes(cal: &Calendar, date: &NaiveDate, day: &Option<Day>, profile: &Profile) -> Option<Vec<ClassSlot>> {
+    day.as_ref().and_then(|d| cal.classes(date, d)).map(|cs| {
+        cs.into_iter()
+            .map(|cell| ClassSlot {
+                // split classes ("Chemistry / Art") become whichever one you picked
+                name: profile.resolve(&cell),
+                timetable: cell,
+                substituted: false,
+                blanked: false,
+                note: None,
+                start: None,
+                end: None,
+            })
+            .collect()
+    })
+}
+
+/// Every conflict between lookup.csv (and recurring.csv) rows on a day.
+pub fn lookup_conflicts(cal: &Calendar, date: &NaiveDate) -> Vec<lookup::Conflict> {
+    let mut classes = timetable_classes(cal, date, &cal.day(date), &Profile::default());
+    lookup::apply(&cal.lookup(date), &mut classes, cal.ctd_modes()).1
+}
@@ -0,0 +1,170 @@
+// Specials
+// anything extra going on: assemblies, trips, deadlines, and days that get
+// cancelled. special.csv rows are
+//   date,text[,until,start,end,category,location,audience]
+// and everything after the text is optional, so the old `date,text` rows
+// (magic *CANC / *CANCSNOW included) still work
+
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use crate::calendar::DATE_FMT;
+use crate::schedule::Cancellation;
+
+const TIME_FMT: &str = "%H:%M";
+
+// nobody's closing the school for more than a summer
+const MAX_SPAN_DAYS: i64 = 120;
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    Assembly,
+    Trip,
+    Deadline,
+    Other,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct Special {
+    pub text: String,
+    pub category: Category,
+    pub start: Option<NaiveTime>,
+    pub end: Option<NaiveTime>,
+    pub location: Option<String>,
+    /// who it's for ("grade 11", "band"), everyone if there's none
+    pub audience: Option<String>,
+    /// the whole thing, for ones that go over more than one day
+    pub from: NaiveDate,
+    pub until: NaiveDate,
+}
+
+/// A day that isn't happening, and why.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    pub kind: Cancellation,
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Specials {
+    by_date: BTreeMap<NaiveDate, Vec<Special>>,
+    cancelled: BTreeMap<NaiveDate, Cancelled>,
+}
+
+impl Specials {
+    /// Adds one row of special.csv. A range goes on every day in it.
+    pub fn add(&mut self, record: &csv::StringRecord) -> Result<(), (usize, String)> {
+        let (special, cancelled) = Self::parse(record)?;
+        let mut d = special.from;
+        while d <= special.until {
+            match &cancelled {
+                // first one wins
+                Some(c) => {
+                    self.cancelled.entry(d).or_insert_with(|| c.clone());
+                },
+                None => self.by_date.entry(d).or_default().push(special.clone()),
+            }
+            d += Duration::days(1);
+        }
+        Ok(())
+    }
+
+    /// One row, and whether it's really a cancellation. Errors have the column.
+    pub fn parse(record: &csv::StringRecord) -> Result<(Special, Option<Cancelled>), (usize, String)> {
+        let opt = |i: usize| record.get(i).map(|s| s.trim()).filter(|s| !s.is_empty());
+        let date = |i: usize| {
+            let s = opt(i).unwrap_or_default();
+            NaiveDate::parse_from_str(s, DATE_FMT).map_err(|_| (i + 1, format!("bad date '{}'", s)))
+        };
+        let time = |i: usize| match opt(i) {
+            Some(s) => NaiveTime::parse_from_str(s, TIME_FMT)
+                .map(Some)
+                .map_err(|_| (i + 1, format!("bad time '{}' (should be hh:mm)", s))),
+            None => Ok(None),
+        };
+
+        let from = date(0)?;
+        let text = match record.get(1) {
+            Some(t) => t.to_string(),
+            None => return Err((2, "no text for this special".to_string())),
+        };
+        let until = if opt(2).is_some() {date(2)?} else {from};
+        if until < from {
+            return Err((3, "ends before it starts".to_string()));
+        }
+        if (until - from).num_days() > MAX_SPAN_DAYS {
+            return Err((3, format!("more than {} days long, probably a typo", MAX_SPAN_DAYS)));
+        }
+        let (start, end) = (time(3)?, time(4)?);
+        if let (Some(s), Some(e)) = (start, end) {
+            if e <= s {
+                return Err((5, "ends before it starts".to_string()));
+            }
+        }
+
+        // the old magic first-special way, or a cancellation category (the text is the reason)
+        let cancelled = match (Cancellation::from_sentinel(&text), opt(5)) {
+            (Some(kind), _) => Some(Cancelled { kind, reason: None }),
+            (None, Some("cancellation")) => Some(Cancelled { kind: Cancellation::Cancelled, reason: Some(text.clone()) }),
+            (None, Some("snow-day")) => Some(Cancelled { kind: Cancellation::SnowDay, reason: Some(text.clone()) }),
+            _ => None,
+        };
+        let category = match opt(5) {
+            None | Some("other") | Some("cancellation") | Some("snow-day") => Category::Other,
+            Some("assembly") => Category::Assembly,
+            Some("trip") => Category::Trip,
+            Some("deadline") => Category::Deadline,
+            Some(c) => return Err((6, format!("'{}' isn't assembly, trip, deadline, cancellation, snow-day or other", c))),
+        };
+
+        let special = Special {
+            text,
+            category,
+            start,
+            end,
+            location: opt(6).map(|s| s.to_string()),
+            audience: opt(7).map(|s| s.to_string()),
+            from,
+            until,
+        };
+        Ok((special, cancelled))
+    }
+
+    /// The special half of a recurring.csv row (see recur.rs): a special.csv
+    /// row without the dates. Errors have recurring.csv's column.
+    pub fn parse_recurring(record: &csv::StringRecord) -> Result<Special, (usize, String)> {
+        let get = |i: usize| record.get(i).unwrap_or_default();
+        if get(5).trim().is_empty() {
+            return Err((6, "no text for this special".to_string()));
+        }
+        // any date will do, each day it lands on gets its own
+        let row = csv::StringRecord::from(vec!["01-01-2000", get(5), "", get(6), get(7), get(8), get(9), get(10)]);
+        match Self::parse(&row) {
+            Ok((_, Some(_))) => Err((9, "cancellations can't repeat, put them in special.csv".to_string())),
+            Ok((special, None)) => Ok(special),
+            Err((column, msg)) => Err((if column == 2 {6} else {column + 3}, msg)),
+        }
+    }
+
+    pub fn on(&self, date: &NaiveDate) -> &[Special] {
+        match self.by_date.get(date) {
+            Some(s) => s,
+            None => &[],
+        }
+    }
+
+    pub fn cancelled(&self, date: &NaiveDate) -> Option<&Cancelled> {
+        self.cancelled.get(date)
+    }
+
+    /// Only if it isn't already.
+    pub fn cancel(&mut self, date: NaiveDate, c: Cancelled) -> bool {
+        if self.cancelled.contains_key(&date) {
+            return false;
+        }
+        self.cancelled.insert(date, c);
+        true
+    }
+}
@@ -2,65 +2,69 @@
 // stupid statistics module
 // idk if i will even use this
 
+use std::collections::{BTreeMap, BTreeSet};
 use chrono::{DateTime, Local, NaiveDate};
 use serde::{Deserialize, Serialize};
-
-const SCHED_DATA: &str = include_str!("sched_data_11.csv");
+use crate::bells::SlotKind;
+use crate::days::Counts;
+use crate::calendar::Calendar;
+use crate::profile::Profile;
+use crate::schedule::{timetable_classes, DayStatus, ScheduleDay};
+use crate::terms::Term;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Stat {
+    /// school days, see Counts in days.rs for what that means
     pub days_rem: usize,
+    pub days_past: usize,
+    /// how much of the school days are done
     pub days_rem_pct: String,
+    /// ped days, holidays and cancelled days
     pub ped_rem: usize,
     pub ped_past: usize,
+    pub exams_rem: usize,
+    pub exams_past: usize,
+    /// time in class slots (no lunch, no breaks) on school days
+    pub hours_rem: f64,
+    pub hours_past: f64,
     pub time_ms: f64,
 }
 
-pub fn generate_stat(dt: DateTime<Local>) -> Stat {
+/// For the whole year, or just one term.
+pub fn generate_stat(cal: &Calendar, dt: DateTime<Local>, term: Option<&Term>) -> Stat {
     let benchmark_dt_start = chrono::Local::now();
-    let date = dt.naive_local().date();
-
-    // load it
-    let mut whole_schedule: Vec<MiniDay> = vec![];
-    let mut reader = csv::ReaderBuilder::new().has_headers(false).from_reader(SCHED_DATA.as_bytes());
-    for r in reader.records() {
-        let record = r.unwrap();
-        if record.get(0).is_some() {
-            let value = record.get(1).expect("corrupt sched csv or sum idk").trim();
-            if value == "W" {continue} // NO WEEKENDS
-            whole_schedule.push(
-                MiniDay {
-                    date: NaiveDate::parse_from_str(record.get(0).unwrap(), "%d-%m-%Y").unwrap(),
-                    is_ped_etc: if value == "P" || value == "C" {true} else {false},
-                }
-            );
-        }
-    }
+    let now = dt.naive_local();
 
-    let days_total = whole_schedule.len();
-    let mut days_passed: usize = 0;
-    let mut ped_count: usize = 0;
-    let mut ped_rem: usize = 0;
+    let mut days = (0, 0);
+    let mut ped = (0, 0);
+    let mut exams = (0, 0);
+    let mut minutes = (0, 0);
+    for (d, day) in cal.days().filter(|(d, _)| in_term(d, term)) {
+        let bells = cal.bells(d, day);
+        // today's over once school's out
+        let past = *d < now.date() || (*d == now.date() && bells.and_then(|b| b.end()).map(|e| now.time() >= e).unwrap_or(false));
+        let tally = |(p, r): &mut (usize, usize)| if past {*p += 1} else {*r += 1};
 
-    let mut future = false;
-
-    for day in whole_schedule {
-        if day.is_ped_etc {
-            ped_count += 1;
-        }
-        match future {
-            false => {
-                // already happened up to now
-                days_passed += 1;
-                if day.date > date {
-                    future = true;
-                }
-            }
-            true => {
-                if day.is_ped_etc {
-                    ped_rem += 1;
+        let counts = match cal.day_types().counts(day) {
+            // a snow day is a day off too
+            Counts::School if cal.cancelled(d).is_some() => Counts::Off,
+            c => c,
+        };
+        match counts {
+            Counts::School => {
+                tally(&mut days);
+                for slot in bells.iter().flat_map(|b| b.slots.iter()).filter(|s| s.kind == SlotKind::Class) {
+                    let length = (slot.end - slot.start).num_minutes();
+                    if *d < now.date() || (*d == now.date() && now.time() >= slot.end) {
+                        minutes.0 += length;
+                    } else {
+                        minutes.1 += length;
+                    }
                 }
-            }
+            },
+            Counts::Exam => tally(&mut exams),
+            Counts::Off => tally(&mut ped),
+            Counts::Nothing => {},
         }
     }
 
@@ -68,19 +72,267 @@ pub fn generate_stat(dt: DateTime<Local>) -> Stat {
     let benchmark_duration = benchmark_dt_end - benchmark_dt_start;
     let benchmark_duration_ms = benchmark_duration.num_microseconds().unwrap() as f64 / 1000.0;
 
-    // debug // println!("{}, {}",days_passed, days_total);
-    let days_rem_pct = (days_passed as f64 / days_total as f64) * 100.0;
+    let days_total = days.0 + days.1;
+    let days_rem_pct = if days_total == 0 {0.0} else {(days.0 as f64 / days_total as f64) * 100.0};
+    let hours = |m: i64| (m as f64 / 60.0 * 100.0).round() / 100.0;
     Stat {
-        days_rem: days_total - days_passed,
+        days_rem: days.1,
+        days_past: days.0,
         days_rem_pct: format!("{:.3}", days_rem_pct),
-        ped_rem,
-        ped_past: ped_count - ped_rem,
+        ped_rem: ped.1,
+        ped_past: ped.0,
+        exams_rem: exams.1,
+        exams_past: exams.0,
+        hours_rem: hours(minutes.1),
+        hours_past: hours(minutes.0),
         time_ms: benchmark_duration_ms
     }
+}
+
+/// How one class's year is going.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ClassStat {
+    pub class: String,
+    /// meetings that already happened (today counts once school's out)
+    pub held: usize,
+    pub left: usize,
+    /// on the timetable, but the day got cancelled
+    pub lost_cancelled: usize,
+    /// on the timetable, but a CTD or lookup.csv took it away
+    pub lost_changed: usize,
+}
+
+fn in_term(date: &NaiveDate, term: Option<&Term>) -> bool {
+    term.map(|t| t.contains(date)).unwrap_or(true)
+}
+
+// only real classes: blanked slots are " ", and lookup.csv puts all sorts of
+// things in (exam times, arrows) that aren't in any timetable
+fn stat<'a>(stats: &'a mut BTreeMap<String, ClassStat>, known: &BTreeSet<String>, name: &str) -> Option<&'a mut ClassStat> {
+    if !known.contains(name) {
+        return None;
+    }
+    Some(stats.entry(name.to_string()).or_insert_with(|| ClassStat {
+        class: name.to_string(),
+        ..Default::default()
+    }))
+}
+
+/// Per-class counts for the whole calendar (or one term), with your picks for
+/// split classes.
+pub fn class_stats(cal: &Calendar, dt: DateTime<Local>, profile: &Profile, term: Option<&Term>) -> Vec<ClassStat> {
+    let now = dt.naive_local();
+    let mut stats: BTreeMap<String, ClassStat> = BTreeMap::new();
+    let known: BTreeSet<String> = cal
+        .timetables()
+        .flatten()
+        .flatten()
+        .map(|cell| profile.resolve(cell))
+        .filter(|name| !name.trim().is_empty())
+        .collect();
+
+    for (date, day) in cal.days().filter(|(d, _)| in_term(d, term)) {
+        // lunch is in the timetable too, but it's not a class
+        let periods = cal.bells(date, day).map(|b| b.periods()).unwrap_or_default();
+        let not_lunch = |i: &usize| periods.get(*i).map(|p| p.kind != SlotKind::Lunch).unwrap_or(true);
+
+        let timetable: Vec<String> = timetable_classes(cal, date, &Some(day.clone()), profile)
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| not_lunch(i))
+            .map(|(_, c)| c.name)
+            .collect();
+        let sd = ScheduleDay::build(cal, *date, now, profile);
+
+        if sd.cancelled.is_some() {
+            for name in &timetable {
+                if let Some(s) = stat(&mut stats, &known, name) {
+                    s.lost_cancelled += 1;
+                }
+            }
+            continue;
+        }
+
+        let happening: Vec<String> = sd.classes
+            .iter()
+            .flatten()
+            .enumerate()
+            .filter(|(i, c)| not_lunch(i) && !c.blanked)
+            .map(|(_, c)| c.name.clone())
+            .collect();
+        let past = *date < now.date() || (*date == now.date() && sd.status == DayStatus::IsOver);
+        for name in &happening {
+            if let Some(s) = stat(&mut stats, &known, name) {
+                if past {s.held += 1} else {s.left += 1}
+            }
+        }
+        // a swap moves a class around, it doesn't lose it
+        let mut seen: Vec<&String> = vec![];
+        for name in &timetable {
+            if seen.contains(&name) {
+                continue;
+            }
+            seen.push(name);
+            let normally = timetable.iter().filter(|n| *n == name).count();
+            let actually = happening.iter().filter(|n| *n == name).count();
+            if let Some(s) = stat(&mut stats, &known, name) {
+                s.lost_changed += normally.saturating_sub(actually);
+            }
+        }
+    }
+
+    stats.into_values().collect()
+}
 
+/// One term's (or semester's) share of the stats.
+#[derive(Serialize, Debug)]
+pub struct TermStat {
+    pub term: Term,
+    pub days: Stat,
+    pub classes: Vec<ClassStat>,
 }
 
-struct MiniDay {
-    date: NaiveDate,
-    is_ped_etc: bool,
-}
\ No newline at end of file
+/// Every term and semester in the calendar, in terms.csv order.
+pub fn term_stats(cal: &Calendar, dt: DateTime<Local>, profile: &Profile) -> Vec<TermStat> {
+    cal.terms()
+        .iter()
+        .map(|t| TermStat {
+            term: t.clone(),
+            days: generate_stat(cal, dt, Some(t)),
+            classes: class_stats(cal, dt, profile, Some(t)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Local, NaiveDate, TimeZone};
+    use crate::bells::Bells;
+    use crate::calendar::{Calendar, Source};
+    use crate::days::DayTypes;
+    use super::*;
+
+    // the baked-in grade 11 calendar with the day types and bells below, and
+    // no runtime files, so editing special.csv can't break these
+    const DAY_TYPES: &str = "\
+1,cycle,Day 1,#ad253e
+2,cycle,Day 2,#6a4823
+3,cycle,Day 3,#296a33
+4,cycle,Day 4,#2f6a5f
+5,cycle,Day 5,#29556a
+6,cycle,Day 6,#3d386a
+7,cycle,Day 7,#6a3a62
+8,cycle,Day 8,#79141e
+9,cycle,Day 9,#56617a
+P,ped,Ped Day,#549ac6
+C,holiday,Holiday,#c68252
+D,holiday-dont-count,Holiday,#c68252
+W,weekend,Weekend,#2b3032
+E,exam,Exam Day,#39b938
+N,no-exam,Exam-free Day,#4e88a3
+NONE,none,No Day,#2b3032
+";
+    // 6 hours of class, and 4 on day 9
+    const BELLS: &str = "\
+regular,class,08:00,09:00
+regular,class,09:05,10:05
+regular,break,10:05,10:25,Break
+regular,class,10:25,11:25
+regular,class,11:30,12:30
+regular,lunch,12:35,13:35
+regular,class,13:40,14:40
+regular,class,14:45,15:45
+half,class,08:00,09:00
+half,class,09:05,10:05
+half,break,10:05,10:25,Break
+half,class,10:25,11:25
+half,class,11:30,12:30
+";
+
+    fn rows(text: &str) -> Vec<csv::StringRecord> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(text.as_bytes())
+            .records()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    fn calendar() -> Calendar {
+        let mut day_types = DayTypes::default();
+        for r in rows(DAY_TYPES) {
+            day_types.add(&r).unwrap();
+        }
+        let mut bells = Bells::default();
+        for r in rows(BELLS) {
+            bells.add_slot(&r).unwrap();
+        }
+        for day in 1..=9 {
+            let schedule = if day == 9 {"half"} else {"regular"};
+            bells.add_day(&csv::StringRecord::from(vec![day.to_string(), schedule.to_string()]), &day_types).unwrap();
+        }
+        Calendar::from_parts(&Source::builtin(), day_types, bells).unwrap()
+    }
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+        let t = NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap();
+        Local.from_local_datetime(&t).earliest().unwrap()
+    }
+
+    fn stat_at(dt: DateTime<Local>) -> Stat {
+        generate_stat(&calendar(), dt, None)
+    }
+
+    #[test]
+    fn known_date() {
+        let s = stat_at(at(2022, 3, 1, 0, 0));
+        assert_eq!((s.days_rem, s.days_past), (61, 110));
+        assert_eq!((s.ped_rem, s.ped_past), (8, 10));
+        assert_eq!((s.exams_rem, s.exams_past), (2, 0));
+        assert_eq!((s.hours_rem, s.hours_past), (352.0, 636.0));
+        assert_eq!(s.days_rem_pct, "64.327");
+    }
+
+    #[test]
+    fn first_day_isnt_over_until_it_is() {
+        // the 30th of august is the first day of classes
+        let s = stat_at(at(2021, 8, 30, 7, 0));
+        assert_eq!((s.days_rem, s.days_past), (171, 0));
+        let s = stat_at(at(2021, 8, 30, 16, 0));
+        assert_eq!((s.days_rem, s.days_past), (170, 1));
+    }
+
+    #[test]
+    fn today_counts_until_the_last_bell() {
+        // a regular day 7: 8:00 to 15:45, 6 hours of class
+        let before = stat_at(at(2022, 3, 8, 7, 59));
+        let during = stat_at(at(2022, 3, 8, 10, 0));
+        let after = stat_at(at(2022, 3, 8, 15, 45));
+        assert_eq!((before.days_rem, before.days_past), (61, 110));
+        assert_eq!((during.days_rem, during.days_past), (61, 110));
+        assert_eq!((after.days_rem, after.days_past), (60, 111));
+        // hours go as each class ends
+        assert_eq!((before.hours_rem, before.hours_past), (352.0, 636.0));
+        assert_eq!((during.hours_rem, during.hours_past), (351.0, 637.0));
+        assert_eq!((after.hours_rem, after.hours_past), (346.0, 642.0));
+    }
+
+    #[test]
+    fn only_school_off_and_exam_days_count() {
+        // NONE, D, N and weekends are in the calendar but count as nothing
+        let data = include_str!("sched_data_11.csv");
+        let codes: Vec<&str> = data.lines().filter_map(|l| l.split(',').nth(1)).collect();
+        for code in ["NONE", "D", "N", "W"] {
+            assert!(codes.contains(&code), "no {} days to test with", code);
+        }
+        let count = |f: &dyn Fn(&str) -> bool| codes.iter().filter(|c| f(c)).count();
+
+        let s = stat_at(at(2021, 8, 1, 0, 0));
+        assert_eq!(s.days_rem + s.days_past, count(&|c| c.parse::<usize>().is_ok()));
+        assert_eq!(s.ped_rem + s.ped_past, count(&|c| c == "P" || c == "C"));
+        assert_eq!(s.exams_rem + s.exams_past, count(&|c| c == "E"));
+        assert_eq!((s.days_rem, s.ped_rem, s.exams_rem), (171, 18, 2));
+    }
+}
@@ -0,0 +1,79 @@
+// Terms
+// terms and semesters. report cards go by term, and split classes like
+// Chemistry / Current Events flip at the semester. terms.csv rows are
+//   kind,name,from,to,grade,classes
+// kind is term or semester, from and to are dd-mm-yyyy (both included). grade
+// and classes can be left off: no grade is every grade, and classes is a
+// timetable file (same as sched_classes.csv) to use instead of the normal one
+// while it's on
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use crate::calendar::DATE_FMT;
+
+pub const TERMS_PATH: &str = "./terms.csv";
+
+#[derive(Clone, Copy, Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TermKind {
+    Term,
+    Semester,
+}
+
+#[derive(Clone, Serialize, Debug)]
+pub struct Term {
+    pub kind: TermKind,
+    pub name: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    #[serde(skip)]
+    pub grade: Option<String>,
+    /// where its timetable came from, for validate and the watcher
+    #[serde(skip)]
+    pub classes_name: Option<String>,
+    #[serde(skip)]
+    pub timetable: Option<Vec<Vec<String>>>,
+}
+
+impl Term {
+    /// One row of terms.csv. The timetable file (if there is one) doesn't get
+    /// read here, see Calendar::load_shared.
+    pub fn parse(record: &csv::StringRecord) -> Result<Self, (usize, String)> {
+        let kind = match record.get(0).unwrap_or_default().trim() {
+            "term" => TermKind::Term,
+            "semester" => TermKind::Semester,
+            k => return Err((1, format!("'{}' isn't term or semester", k))),
+        };
+        let name = record.get(1).unwrap_or_default().trim();
+        if name.is_empty() {
+            return Err((2, "no name".to_string()));
+        }
+        let date = |i: usize| {
+            let s = record.get(i).unwrap_or_default().trim();
+            NaiveDate::parse_from_str(s, DATE_FMT).map_err(|_| (i + 1, format!("bad date '{}' (should be dd-mm-yyyy)", s)))
+        };
+        let (from, to) = (date(2)?, date(3)?);
+        if to < from {
+            return Err((4, "it ends before it starts".to_string()));
+        }
+        let optional = |i: usize| Some(record.get(i)?.trim()).filter(|s| !s.is_empty()).map(|s| s.to_string());
+
+        Ok(Term {
+            kind,
+            name: name.to_string(),
+            from,
+            to,
+            grade: optional(4),
+            classes_name: optional(5),
+            timetable: None,
+        })
+    }
+
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        (self.from..=self.to).contains(date)
+    }
+
+    pub fn is_for(&self, grade: &str) -> bool {
+        self.grade.as_deref().map(|g| g == grade).unwrap_or(true)
+    }
+}
@@ -0,0 +1,393 @@
+// Validation
+// goes through every data file and complains about everything it finds,
+// instead of stopping at the first problem like Calendar::load does
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+use crate::bells::{Bells, BELLS_PATH, BELL_DAYS_PATH};
+use crate::days::{Day, DayTypes, DAY_TYPES_PATH};
+use crate::ctd::{CtdModes, CTD_MODES_PATH};
+use crate::markup;
+use crate::lookup::{Action, LookupRow, Target};
+use crate::schedule::{lookup_conflicts, Cancellation};
+use crate::specials::Specials;
+use crate::calendar::{Registry, Source, CANCEL_MODES_PATH, DATE_FMT, LOOKUP_PATH, ONLINE_PATH, RECURRING_PATH, SPECIALS_PATH};
+use crate::recur::Recurrence;
+use crate::terms::{Term, TERMS_PATH};
+
+// every date in a calendar, with the line it's on
+type Days = BTreeMap<NaiveDate, (u64, Day)>;
+
+#[derive(Debug)]
+pub struct Issue {
+    pub file: String,
+    pub line: u64,
+    pub column: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.msg)
+    }
+}
+
+#[derive(Default)]
+struct Checker {
+    issues: Vec<Issue>,
+}
+
+impl Checker {
+    fn issue(&mut self, file: &str, line: u64, column: usize, msg: String) {
+        self.issues.push(Issue {
+            file: file.to_string(),
+            line,
+            column,
+            msg,
+        });
+    }
+
+    /// Reads every record it can. Broken ones get reported and skipped.
+    fn records(&mut self, file: &str, reader: csv::Reader<impl std::io::Read>) -> Vec<(u64, csv::StringRecord)> {
+        let mut records = vec![];
+        for r in reader.into_records() {
+            match r {
+                Ok(record) => {
+                    let line = record.position().map(|p| p.line()).unwrap_or_default();
+                    records.push((line, record));
+                },
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or_default();
+                    self.issue(file, line, 0, e.to_string());
+                },
+            }
+        }
+        records
+    }
+
+    fn runtime_records(&mut self, file: &str) -> Vec<(u64, csv::StringRecord)> {
+        match csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .comment(Some(b'#'))
+            .from_path(file)
+        {
+            Ok(r) => self.records(file, r),
+            Err(_) => vec![], // missing is fine, they're optional
+        }
+    }
+
+    fn date(&mut self, file: &str, line: u64, s: Option<&str>) -> Option<NaiveDate> {
+        let s = s.unwrap_or_default();
+        match NaiveDate::parse_from_str(s, DATE_FMT) {
+            Ok(d) => Some(d),
+            Err(_) => {
+                self.issue(file, line, 1, format!("bad date '{}' (should be dd-mm-yyyy)", s));
+                None
+            },
+        }
+    }
+}
+
+/// Checks everything. An empty list means all good.
+pub fn validate() -> Vec<Issue> {
+    let mut c = Checker::default();
+
+    // day types: everything else is checked against these
+    let mut day_types = DayTypes::default();
+    for (line, record) in c.runtime_records(DAY_TYPES_PATH) {
+        if let Err((column, msg)) = day_types.add(&record) {
+            c.issue(DAY_TYPES_PATH, line, column, msg);
+        }
+    }
+    if day_types.is_empty() {
+        c.issue(DAY_TYPES_PATH, 0, 0, "no day types, is the file missing?".to_string());
+    }
+
+    // bells: times make sense
+    let mut bells = Bells::default();
+    for (line, record) in c.runtime_records(BELLS_PATH) {
+        if let Err((column, msg)) = bells.add_slot(&record) {
+            c.issue(BELLS_PATH, line, column, msg);
+        }
+    }
+    for (line, record) in c.runtime_records(BELL_DAYS_PATH) {
+        if let Err((column, msg)) = bells.add_day(&record, &day_types) {
+            c.issue(BELL_DAYS_PATH, line, column, msg);
+        }
+    }
+
+    // ctd modes: real bell schedules, colors and periods
+    let mut ctd_modes = CtdModes::default();
+    for (line, record) in c.runtime_records(CTD_MODES_PATH) {
+        if let Err((column, msg)) = ctd_modes.add(&record, &bells) {
+            c.issue(CTD_MODES_PATH, line, column, msg);
+        }
+    }
+
+    // every calendar: the baked-in one and whatever calendars.csv adds
+    let sources = match Registry::sources() {
+        Ok(s) => s,
+        Err(e) => {
+            c.issue(&e.file, e.line, 0, e.msg);
+            vec![Source::builtin()]
+        },
+    };
+    let calendars: Vec<(Days, Vec<usize>)> = sources
+        .iter()
+        .map(|source| check_calendar(&mut c, &day_types, &bells, source))
+        .collect();
+
+    // lookup: real dates, periods that exist on that day, modes that exist
+    for (line, record) in c.runtime_records(LOOKUP_PATH) {
+        let date = c.date(LOOKUP_PATH, line, record.get(0));
+        // every calendar that has this date (other grades, usually)
+        let on: Vec<(&Day, &Vec<usize>)> = calendars
+            .iter()
+            .filter_map(|(days, timetable)| date.and_then(|d| days.get(&d)).map(|(_, day)| (day, timetable)))
+            .collect();
+        let day = on.first().map(|(day, _)| *day);
+
+        if let (Some(d), None) = (date, day) {
+            c.issue(LOOKUP_PATH, line, 1, format!("{} isn't in the calendar", d.format(DATE_FMT)));
+        }
+
+        let row = match LookupRow::parse(&record, 1, &bells, &ctd_modes, LOOKUP_PATH, line) {
+            Ok(row) => row,
+            Err((column, msg)) => {
+                c.issue(LOOKUP_PATH, line, column, msg);
+                continue;
+            },
+        };
+        if let Some((p, column, extra)) = period_of(&row.action, &record, 1) {
+            // fine if it's fine for any of them
+            let periods = on
+                .iter()
+                .filter_map(|(day, timetable)| match day {
+                    Day::Exam => Some(1),
+                    d => d.cycle_index().and_then(|i| timetable.get(i).cloned()),
+                })
+                .max();
+            match periods {
+                Some(n) if p > n + extra => {
+                    c.issue(LOOKUP_PATH, line, column, format!("period {} is out of range, that day has {}", p, n));
+                },
+                None if day.is_some() => {
+                    c.issue(LOOKUP_PATH, line, column, format!("period {} on a day with no classes", p));
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // specials: the same checks loading does, but all of them
+    let mut specials = Specials::default();
+    for (line, record) in c.runtime_records(SPECIALS_PATH) {
+        if let Err((column, msg)) = specials.add(&record) {
+            c.issue(SPECIALS_PATH, line, column, msg);
+        }
+        for url in markup::bad_links(record.get(1).unwrap_or_default()) {
+            c.issue(SPECIALS_PATH, line, 2, format!("'{}' won't be a link, only http(s), mailto and /paths", url));
+        }
+    }
+    // recurring: the same as loading, plus periods that exist on that day
+    for (line, record) in c.runtime_records(RECURRING_PATH) {
+        let recurrence = match Recurrence::parse(&record, &day_types) {
+            Ok(r) => r,
+            Err((column, msg)) => {
+                c.issue(RECURRING_PATH, line, column, msg);
+                continue;
+            },
+        };
+        match record.get(0).unwrap_or_default() {
+            "SPECIAL" => {
+                if let Err((column, msg)) = Specials::parse_recurring(&record) {
+                    c.issue(RECURRING_PATH, line, column, msg);
+                }
+                for url in markup::bad_links(record.get(5).unwrap_or_default()) {
+                    c.issue(RECURRING_PATH, line, 6, format!("'{}' won't be a link, only http(s), mailto and /paths", url));
+                }
+            },
+            "LOOKUP" => match LookupRow::parse(&record, 5, &bells, &ctd_modes, RECURRING_PATH, line) {
+                Ok(row) => {
+                    if let Some((p, column, extra)) = period_of(&row.action, &record, 5) {
+                        // a weekday one could be any day of the cycle
+                        let periods = calendars
+                            .iter()
+                            .flat_map(|(_, timetable)| match recurrence.cycle_day() {
+                                Some(n) => timetable.get(n - 1).copied().into_iter().collect(),
+                                None => timetable.clone(),
+                            })
+                            .max()
+                            .unwrap_or_default();
+                        if p > periods + extra {
+                            c.issue(RECURRING_PATH, line, column, format!("period {} is out of range, that day has {}", p, periods));
+                        }
+                    }
+                },
+                Err((column, msg)) => c.issue(RECURRING_PATH, line, column, msg),
+            },
+            other => c.issue(RECURRING_PATH, line, 1, format!("'{}' isn't SPECIAL or LOOKUP", other)),
+        }
+    }
+
+    // terms: dates in a calendar, timetables that fit, no two terms (or two
+    // semesters) at once
+    let mut terms: Vec<(u64, Term)> = vec![];
+    for (line, record) in c.runtime_records(TERMS_PATH) {
+        let term = match Term::parse(&record) {
+            Ok(t) => t,
+            Err((column, msg)) => {
+                c.issue(TERMS_PATH, line, column, msg);
+                continue;
+            },
+        };
+        let grades: Vec<&str> = sources.iter().map(|s| s.grade.as_str()).filter(|g| term.is_for(g)).collect();
+        if grades.is_empty() {
+            c.issue(TERMS_PATH, line, 5, format!("there's no grade {} calendar", term.grade.as_deref().unwrap_or_default()));
+        }
+        let in_a_calendar = sources
+            .iter()
+            .zip(calendars.iter())
+            .any(|(s, (days, _))| term.is_for(&s.grade) && days.contains_key(&term.from) && days.contains_key(&term.to));
+        if !grades.is_empty() && !in_a_calendar {
+            c.issue(TERMS_PATH, line, 3, format!("{} to {} isn't all in one calendar", term.from.format(DATE_FMT), term.to.format(DATE_FMT)));
+        }
+        if let Some(path) = &term.classes_name {
+            match std::fs::read_to_string(path) {
+                Ok(text) => {
+                    check_timetable(&mut c, &day_types, &bells, path, &text);
+                },
+                Err(e) => c.issue(TERMS_PATH, line, 6, format!("can't read '{}' ({})", path, e)),
+            }
+        }
+        if let Some((other, t)) = terms.iter().find(|(_, t)| {
+            t.kind == term.kind
+                && t.from <= term.to
+                && t.to >= term.from
+                && grades.iter().any(|g| t.is_for(g))
+        }) {
+            c.issue(TERMS_PATH, line, 3, format!("overlaps {} (line {})", t.name, other));
+        }
+        terms.push((line, term));
+    }
+
+    // online: just dates
+    for (line, record) in c.runtime_records(ONLINE_PATH) {
+        c.date(ONLINE_PATH, line, record.get(0));
+    }
+
+    // cancel modes: a cancellation and what it does
+    for (line, record) in c.runtime_records(CANCEL_MODES_PATH) {
+        if Cancellation::from_sentinel(record.get(0).unwrap_or_default()).is_none() {
+            c.issue(CANCEL_MODES_PATH, line, 1, format!("'{}' isn't *CANC or *CANCSNOW", record.get(0).unwrap_or_default()));
+        }
+        let mode = record.get(1).unwrap_or_default().trim();
+        if mode != "skip" && mode != "shift" {
+            c.issue(CANCEL_MODES_PATH, line, 2, format!("'{}' isn't skip or shift", mode));
+        }
+    }
+
+    // conflicts: only worth looking for once everything loads
+    if c.issues.is_empty() {
+        if let Ok(reg) = Registry::load() {
+            for cal in reg.calendars() {
+                for (date, _) in cal.days() {
+                    for conflict in lookup_conflicts(cal, date) {
+                        let msg = format!("on {} (grade {}): {}", date.format(DATE_FMT), cal.grade(), conflict.msg);
+                        c.issue(conflict.file, conflict.line, 0, msg);
+                    }
+                }
+            }
+        }
+    }
+
+    c.issues
+}
+
+/// The period a lookup row (starting at `record[at]`) points at by number,
+/// the column it's in, and how far past the last period it's allowed to go.
+fn period_of(action: &Action, record: &csv::StringRecord, at: usize) -> Option<(usize, usize, usize)> {
+    // date,3,Drama has the period where the others have the what
+    let old_way = record.get(at).map(|w| w.trim().parse::<usize>().is_ok()).unwrap_or(false);
+    let (p, i, extra) = match action {
+        Action::Replace(Target::Period(p), _) if old_way => (*p, 0, 0),
+        Action::Replace(Target::Period(p), _) | Action::Delete(Target::Period(p)) | Action::Note(Target::Period(p), _) => (*p, 1, 0),
+        Action::Swap(Target::Period(p), _) => (*p, 1, 0),
+        Action::Swap(_, Target::Period(p)) => (*p, 2, 0),
+        // one past the end is adding a period at the end
+        Action::Insert(p, _) => (*p, 1, 1),
+        _ => return None,
+    };
+    Some((p, at + i + 1, extra))
+}
+
+/// One calendar's days and timetable. Gives back the days (with their lines)
+/// and how many classes each day of the cycle has, for checking lookup.csv.
+fn check_calendar(c: &mut Checker, day_types: &DayTypes, bells: &Bells, source: &Source) -> (Days, Vec<usize>) {
+    // sched data: dates in order, one per day, known codes
+    let reader = csv::ReaderBuilder::new().has_headers(false).from_reader(source.data.as_bytes());
+    let mut days: Days = BTreeMap::new();
+    let mut prev: Option<NaiveDate> = None;
+    for (line, record) in c.records(&source.data_name, reader) {
+        let date = match c.date(&source.data_name, line, record.get(0)) {
+            Some(d) => d,
+            None => continue,
+        };
+        let code = record.get(1).unwrap_or_default();
+        let day = day_types.parse(code);
+        if let Day::Unknown = day {
+            c.issue(&source.data_name, line, 2, format!("unknown day code '{}'", code));
+        }
+
+        if let Some(p) = prev {
+            if date <= p {
+                c.issue(&source.data_name, line, 1, format!("{} comes after {}, out of order", date.format(DATE_FMT), p.format(DATE_FMT)));
+            } else if date != p + Duration::days(1) {
+                c.issue(&source.data_name, line, 1, format!("gap: nothing between {} and {}", p.format(DATE_FMT), date.format(DATE_FMT)));
+            }
+        }
+        prev = Some(date);
+
+        if let Some((first, _)) = days.get(&date) {
+            c.issue(&source.data_name, line, 1, format!("duplicate date, already on line {}", first));
+        } else {
+            days.insert(date, (line, day));
+        }
+    }
+
+    let timetable = check_timetable(c, day_types, bells, &source.classes_name, &source.classes);
+    (days, timetable)
+}
+
+/// A timetable (sched_classes.csv or a term's). Gives back how many classes
+/// each day of the cycle has.
+fn check_timetable(c: &mut Checker, day_types: &DayTypes, bells: &Bells, name: &str, text: &str) -> Vec<usize> {
+    // blanks are only allowed off the end of a row
+    let reader = csv::ReaderBuilder::new().has_headers(false).from_reader(text.as_bytes());
+    let mut timetable: Vec<usize> = vec![];
+    for (line, record) in c.records(name, reader) {
+        let len = record.iter().enumerate().filter(|(_, x)| !x.is_empty()).map(|(i, _)| i + 1).last().unwrap_or(0);
+        for (i, class) in record.iter().enumerate().take(len) {
+            if class.is_empty() {
+                c.issue(name, line, i + 1, "blank class in the middle of the day".to_string());
+            }
+        }
+        timetable.push(len);
+    }
+    if timetable.len() != day_types.cycle_length() {
+        c.issue(name, 0, 0, format!("the cycle is {} days long but there are {} days of classes", day_types.cycle_length(), timetable.len()));
+    }
+
+    for (i, len) in timetable.iter().enumerate() {
+        if let Some(b) = bells.for_day(&Day::Cycle(i + 1)) {
+            if b.periods().len() < *len {
+                c.issue(BELL_DAYS_PATH, 0, 0, format!("day {} has {} classes but '{}' only has {} periods", i + 1, len, b.name, b.periods().len()));
+            }
+        }
+    }
+
+    timetable
+}
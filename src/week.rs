@@ -0,0 +1,104 @@
+// Week
+// the grid we actually plan the week with: a column per school day (colored
+// like its block), a row per period, overrides and specials right in there.
+// ped days, holidays and cancelled days just get a narrow "no school" column
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+use serde::Serialize;
+use crate::bells;
+use crate::blocks::Block;
+use crate::calendar::{Registry, ISO_FMT};
+use crate::days::Day;
+use crate::profile::Profile;
+use crate::schedule::ScheduleDay;
+
+#[derive(Serialize, Debug)]
+pub struct WeekCell {
+    name: String,
+    substituted: bool,
+    blanked: bool,
+    note: Option<String>,
+    time: String,
+    /// happening right now
+    now: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WeekRow {
+    period: usize,
+    /// one per day, None if that day doesn't have this many periods
+    cells: Vec<Option<WeekCell>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Week {
+    title: String,
+    prev: String,
+    next: String,
+    days: Vec<WeekDay>,
+    rows: Vec<WeekRow>,
+}
+
+/// A column header: the day, its color and its specials.
+#[derive(Serialize, Debug)]
+pub struct WeekDay {
+    block: Block,
+    today: bool,
+    /// false if there are no classes at all (ped day, holiday, cancelled)
+    school: bool,
+}
+
+/// The week (monday to sunday, minus weekends and NONE days) that `date` is in.
+pub fn build(reg: &Registry, grade: Option<&str>, code: &str, date: NaiveDate, now: NaiveDateTime) -> Week {
+    let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+
+    let mut days = vec![];
+    let mut columns: Vec<ScheduleDay> = vec![];
+    for date in (0..7).map(|i| monday + Duration::days(i)) {
+        let cal = reg.select(grade, date);
+        let sd = ScheduleDay::build(cal, date, now, &Profile::decode(cal, code));
+        if let None | Some(Day::Weekend) | Some(Day::NoSchool) = sd.day {
+            continue;
+        }
+        // the date already says which day it is
+        let title = if date == now.date() {"Today"} else {""};
+        days.push(WeekDay {
+            block: Block::from_day(&sd, cal.day_types(), title),
+            today: date == now.date(),
+            school: sd.classes.is_some(),
+        });
+        columns.push(sd);
+    }
+
+    let periods = columns.iter().map(|sd| sd.classes.as_ref().map(|c| c.len()).unwrap_or(0)).max().unwrap_or(0);
+    let rows = (0..periods)
+        .map(|i| WeekRow {
+            period: i + 1,
+            cells: columns
+                .iter()
+                .map(|sd| {
+                    let c = sd.classes.as_ref()?.get(i)?;
+                    Some(WeekCell {
+                        name: c.name.clone(),
+                        substituted: c.substituted,
+                        blanked: c.blanked,
+                        note: c.note.clone(),
+                        time: match (c.start, c.end) {
+                            (Some(s), Some(e)) => bells::format_range(s, e),
+                            _ => String::new(),
+                        },
+                        now: sd.current.as_ref().and_then(|c| c.period) == Some(i),
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    Week {
+        title: format!("Week of {}", monday.format("%A, %d-%b-%Y")),
+        prev: (monday - Duration::days(7)).format(ISO_FMT).to_string(),
+        next: (monday + Duration::days(7)).format(ISO_FMT).to_string(),
+        days,
+        rows,
+    }
+}
@@ -0,0 +1,129 @@
+// What if
+// "what happens if we cancel friday" without touching any of the real files:
+// do the edits to a copy of the calendar and show what changed
+
+use chrono::{DateTime, Duration, Local, NaiveDate};
+use serde::Serialize;
+use crate::calendar::{Calendar, ISO_FMT};
+use crate::profile::Profile;
+use crate::schedule::{Cancellation, ScheduleDay};
+use crate::specials::Cancelled;
+use crate::stat::{generate_stat, Stat};
+
+// how far past the first edit a what-if shows
+pub const DEFAULT_WEEKS: i64 = 3;
+pub const MAX_WEEKS: i64 = 12;
+
+#[derive(Clone, Copy, Serialize, Debug)]
+#[serde(tag = "edit", rename_all = "lowercase")]
+pub enum Edit {
+    Cancel { date: NaiveDate },
+    Snow { date: NaiveDate },
+    Ped { date: NaiveDate },
+    Swap { a: NaiveDate, b: NaiveDate },
+}
+
+impl Edit {
+    /// `cancel:2022-03-21`, `snow:...`, `ped:...` or `swap:2022-03-21:2022-03-22`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = s.trim().split(':').collect();
+        let date = |i: usize| {
+            let d = parts.get(i).cloned().unwrap_or_default();
+            NaiveDate::parse_from_str(d, ISO_FMT).map_err(|_| format!("'{}' in '{}' isn't a date, use yyyy-mm-dd", d, s))
+        };
+        let edit = match parts[0] {
+            "cancel" => Edit::Cancel { date: date(1)? },
+            "snow" => Edit::Snow { date: date(1)? },
+            "ped" => Edit::Ped { date: date(1)? },
+            "swap" => Edit::Swap { a: date(1)?, b: date(2)? },
+            e => return Err(format!("'{}' isn't cancel, snow, ped or swap", e)),
+        };
+        let expected = if let Edit::Swap { .. } = edit {3} else {2};
+        if parts.len() != expected {
+            return Err(format!("'{}' has the wrong number of parts", s));
+        }
+        Ok(edit)
+    }
+
+    /// A list of them, separated by commas.
+    pub fn parse_list(s: &str) -> Result<Vec<Self>, String> {
+        s.split(',').filter(|e| !e.trim().is_empty()).map(Self::parse).collect()
+    }
+
+    pub fn dates(&self) -> Vec<NaiveDate> {
+        match *self {
+            Edit::Cancel { date } | Edit::Snow { date } | Edit::Ped { date } => vec![date],
+            Edit::Swap { a, b } => vec![a, b],
+        }
+    }
+
+    fn apply(&self, cal: &mut Calendar) {
+        match *self {
+            Edit::Cancel { date } => cal.cancel(date, Cancelled { kind: Cancellation::Cancelled, reason: None }),
+            Edit::Snow { date } => cal.cancel(date, Cancelled { kind: Cancellation::SnowDay, reason: None }),
+            Edit::Ped { date } => cal.make_ped(date),
+            Edit::Swap { a, b } => cal.swap_days(a, b),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct WhatIfDay {
+    pub date: NaiveDate,
+    /// the label it has now
+    pub before: String,
+    pub after: ScheduleDay,
+    pub changed: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub struct WhatIf {
+    pub edits: Vec<Edit>,
+    /// from the first edited day, for however many weeks were asked for
+    pub days: Vec<WhatIfDay>,
+    pub stat_before: Stat,
+    pub stat_after: Stat,
+}
+
+/// Does the edits to a copy of `cal`. Every date has to be in it.
+pub fn simulate(cal: &Calendar, edits: Vec<Edit>, now: DateTime<Local>, code: &str, weeks: i64) -> Result<WhatIf, String> {
+    if !(1..=MAX_WEEKS).contains(&weeks) {
+        return Err(format!("weeks has to be 1 to {}", MAX_WEEKS));
+    }
+    let dates: Vec<NaiveDate> = edits.iter().flat_map(|e| e.dates()).collect();
+    if let Some(d) = dates.iter().find(|d| cal.day(d).is_none()) {
+        return Err(format!("{} isn't in the calendar", d.format(ISO_FMT)));
+    }
+    let from = match dates.iter().min() {
+        Some(d) => *d,
+        None => return Err("no edits".to_string()),
+    };
+
+    let mut after = cal.clone();
+    for e in &edits {
+        e.apply(&mut after);
+    }
+
+    let profile = Profile::decode(cal, code);
+    let days = (0..weeks * 7)
+        .map(|i| from + Duration::days(i))
+        .filter(|d| cal.day(d).is_some())
+        .map(|date| {
+            let old = ScheduleDay::build(cal, date, now.naive_local(), &profile);
+            let new = ScheduleDay::build(&after, date, now.naive_local(), &profile);
+            WhatIfDay {
+                date,
+                changed: old.label != new.label || old.cancelled != new.cancelled,
+                before: old.label,
+                after: new,
+            }
+        })
+        .collect();
+
+    Ok(WhatIf {
+        edits,
+        days,
+        stat_before: generate_stat(cal, now, None),
+        stat_after: generate_stat(&after, now, None),
+    })
+}